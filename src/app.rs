@@ -1,208 +1,1262 @@
-use ratatui::style::Color;
+use anyhow::{Result, anyhow};
+use ratatui::layout::{Position, Rect};
 use ratatui::widgets::TableState;
+use serde::Deserialize;
 use std::cmp::Ordering;
-use sysinfo::{Pid, System};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::time::{Duration, Instant};
+use sysinfo::{Groups, Pid, ProcessStatus, Signal, System, Uid, Users};
+
+use crate::cli::Cli;
+use crate::columns::Column;
+use crate::command_history::CommandHistory;
+use crate::config::{Config, Panels, save_columns};
+use crate::filter::Filter;
+use crate::history::History;
+use crate::keymap::Keymap;
+use crate::priority::{
+    DEFAULT_IO_LEVEL, IO_LEVELS, IoClass, IoPriority, NICE_RANGE, io_priority, set_io_priority,
+    set_nice,
+};
+use crate::procfs::{self, ProcExtra};
+use crate::signals::{SIGNALS, SignalChoice, TreeSignalOrder, send_signal};
+use crate::theme::{AppTheme, ThemeRegistry};
+use crate::throttle::Throttles;
+use crate::tree::{build_tree, subtree_pids};
+
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(5);
+const USERS_REFRESH_TICKS: u64 = 40;
 
 //-----------------------------------------------------------------------------------------------------------------
 
-pub struct ColorTheme {
-    pub base: Color,
-    pub mantle: Color,
-    pub text: Color,
-    pub mauve: Color,
-    pub pink: Color,
-    pub yellow: Color,
-    pub highlight_bg: Color,
-    pub highlight_fg: Color,
+pub struct MemoryHistory {
+    pub used: History<u64>,
+    pub available: History<u64>,
+    pub cached: History<u64>,
+    pub swap_used: History<u64>,
+}
+
+impl MemoryHistory {
+    pub fn new(capacity: usize) -> Self {
+        MemoryHistory {
+            used: History::new(capacity),
+            available: History::new(capacity),
+            cached: History::new(capacity),
+            swap_used: History::new(capacity),
+        }
+    }
+
+    pub fn record(&mut self, sys: &System) {
+        self.used.push(sys.used_memory());
+        self.available.push(sys.available_memory());
+        // sysinfo has no cached figure; what is available beyond truly free memory is
+        // reclaimable page cache and buffers.
+        self.cached
+            .push(sys.available_memory().saturating_sub(sys.free_memory()));
+        self.swap_used.push(sys.used_swap());
+    }
 }
 
-#[derive(Debug, PartialEq, Clone)]
-pub enum AppTheme {
-    Nord,
-    Gruvbox,
-    SolarizedDark,
-    MononokaiPro,
-    GitHub,
-    OrangeSunset,
+#[derive(Debug, PartialEq)]
+pub enum AppMode {
+    Normal,
+    Command,
+    Filtering,
+    Search,
+    SignalPicker,
+    ColumnPicker,
+    IoPriority,
+    Details,
+    Help,
 }
 
-impl AppTheme {
-    pub fn get_colors(&self) -> ColorTheme {
+impl AppMode {
+    pub fn label(&self) -> &'static str {
         match self {
-            // This is the theme you labeled "github dark" in the comments.
-            AppTheme::Nord => ColorTheme {
-                base: Color::Rgb(46, 52, 64),
-                mantle: Color::Rgb(46, 52, 64),
-                text: Color::Rgb(216, 222, 233),
-                mauve: Color::Rgb(163, 190, 140),
-                pink: Color::Rgb(180, 142, 173),
-                yellow: Color::Rgb(136, 192, 208),
-                highlight_bg: Color::Rgb(76, 86, 106),
-                highlight_fg: Color::Rgb(236, 239, 244),
-            },
-            AppTheme::Gruvbox => ColorTheme {
-                base: Color::Rgb(40, 40, 40),
-                mantle: Color::Rgb(40, 40, 40),
-                text: Color::Rgb(235, 219, 178),
-                mauve: Color::Rgb(104, 157, 106),
-                pink: Color::Rgb(214, 93, 14),
-                yellow: Color::Rgb(215, 153, 33),
-                highlight_bg: Color::Rgb(69, 133, 136),
-                highlight_fg: Color::Rgb(40, 40, 40),
-            },
-            // This is the custom GitHub theme you defined in your original match.
-            AppTheme::GitHub => ColorTheme {
-                base: Color::Rgb(13, 17, 23),
-                mantle: Color::Rgb(13, 17, 23),
-                text: Color::Rgb(201, 209, 217),
-                mauve: Color::Rgb(88, 166, 255),
-                pink: Color::Rgb(188, 140, 255),
-                yellow: Color::Rgb(139, 148, 158),
-                highlight_bg: Color::Rgb(33, 38, 45),
-                highlight_fg: Color::Rgb(88, 166, 255),
-            },
-            AppTheme::SolarizedDark => ColorTheme {
-                base: Color::Rgb(0, 43, 54),
-                mantle: Color::Rgb(0, 43, 54),
-                text: Color::Rgb(131, 148, 150),
-                mauve: Color::Rgb(42, 161, 152),
-                pink: Color::Rgb(38, 139, 210),
-                yellow: Color::Rgb(181, 137, 0),
-                highlight_bg: Color::Rgb(88, 110, 117),
-                highlight_fg: Color::Rgb(0, 43, 54),
-            },
-            AppTheme::MononokaiPro => ColorTheme {
-                base: Color::Rgb(45, 42, 46),
-                mantle: Color::Rgb(45, 42, 46),
-                text: Color::Rgb(252, 252, 250),
-                mauve: Color::Rgb(169, 220, 118),
-                pink: Color::Rgb(255, 216, 102),
-                yellow: Color::Rgb(255, 97, 136),
-                highlight_bg: Color::Rgb(120, 220, 232),
-                highlight_fg: Color::Rgb(252, 252, 250),
-            },
-            // This is the first, unnamed theme from your comments.
-            AppTheme::OrangeSunset => ColorTheme {
-                base: Color::Rgb(245, 124, 0),
-                mantle: Color::Rgb(245, 124, 0),
-                text: Color::Rgb(253, 253, 253),
-                mauve: Color::Rgb(0, 180, 255),
-                pink: Color::Rgb(0, 180, 255),
-                yellow: Color::Rgb(255, 224, 178),
-                highlight_bg: Color::Rgb(245, 245, 245),
-                highlight_fg: Color::Rgb(255, 165, 0),
-            },
-        }
-    }
-
-    pub fn from_str(s: &str) -> Option<AppTheme> {
-        match s.to_lowercase().as_str() {
-            "nord" => Some(AppTheme::Nord),
-            "gruvbox" => Some(AppTheme::Gruvbox),
-            "solarizeddark" => Some(AppTheme::SolarizedDark),
-            "orangesunset" => Some(AppTheme::OrangeSunset),
-            "github" => Some(AppTheme::GitHub),
-            "mononokaipro" => Some(AppTheme::MononokaiPro),
-            _ => None,
+            AppMode::Normal => "NORMAL",
+            AppMode::Command => "COMMAND",
+            AppMode::Filtering => "FILTER",
+            AppMode::Search => "SEARCH",
+            AppMode::SignalPicker => "SIGNAL",
+            AppMode::ColumnPicker => "COLUMNS",
+            AppMode::IoPriority => "IONICE",
+            AppMode::Details => "DETAILS",
+            AppMode::Help => "HELP",
         }
     }
+}
 
-    pub fn as_str(&self) -> &'static str {
-        match self {
-            AppTheme::Nord => "Nord",
-            AppTheme::GitHub => "GitHub",
-            AppTheme::Gruvbox => "Gruvbox",
-            AppTheme::SolarizedDark => "SolarizedDark",
-            AppTheme::OrangeSunset => "OrangeSunset",
-            AppTheme::MononokaiPro => "MononokaiPro",
+pub struct SignalDialog {
+    // In the order they are signalled.
+    pub targets: Vec<Target>,
+    pub subtree: Option<SubtreeSignal>,
+    pub selected: usize,
+    pub custom_number: String,
+    pub confirming: bool,
+}
+
+impl SignalDialog {
+    pub fn custom_index() -> usize {
+        SIGNALS.len()
+    }
+
+    pub fn next(&mut self) {
+        self.selected = (self.selected + 1) % (Self::custom_index() + 1);
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = self.selected.checked_sub(1).unwrap_or(Self::custom_index());
+    }
+
+    pub fn choice(&self) -> Option<SignalChoice> {
+        match SIGNALS.get(self.selected) {
+            Some(info) => Some(SignalChoice::Named(info.signal)),
+            None => self.custom_number.parse().ok().map(SignalChoice::Custom),
         }
     }
+}
+
+// The processes whose subtrees make up the targets of a signal, and which end goes first.
+pub struct SubtreeSignal {
+    pub roots: Vec<Target>,
+    pub order: TreeSignalOrder,
+}
+
+// A process an action is aimed at, named for the messages about it.
+pub struct Target {
+    pub pid: u32,
+    pub name: String,
+}
 
-    pub fn variants() -> Vec<&'static str> {
-        vec![
-            "Nord",
-            "Gruvbox",
-            "SolarizedDark",
-            "OrangeSunset",
-            "GitHub",
-            "MononokaiPro",
-        ]
+impl Target {
+    // "1234 (name): reason", for reports of what went wrong.
+    fn failure(&self, err: io::Error) -> String {
+        format!("{} ({}): {}", self.pid, self.name, err)
     }
+}
 
-    pub fn next(self) -> Self {
-        match self {
-            AppTheme::Nord => AppTheme::Gruvbox,
-            AppTheme::Gruvbox => AppTheme::MononokaiPro,
-            AppTheme::MononokaiPro => AppTheme::OrangeSunset,
-            AppTheme::OrangeSunset => AppTheme::GitHub,
-            AppTheme::GitHub => AppTheme::SolarizedDark,
-            AppTheme::SolarizedDark => AppTheme::Nord,
+// Takes lazy-top's own process out of the targets of a stop or throttle: once stopped, nothing
+// would be left to resume it. True if it was there.
+fn remove_self(targets: &mut Vec<Target>) -> bool {
+    let own_pid = std::process::id();
+    let before = targets.len();
+    targets.retain(|target| target.pid != own_pid);
+    targets.len() < before
+}
+
+// The I/O class and level being chosen for the targets, starting from the current setting.
+pub struct IoPriorityDialog {
+    pub targets: Vec<Target>,
+    pub priority: IoPriority,
+}
+
+impl IoPriorityDialog {
+    pub fn next(&mut self) {
+        let index = IoClass::ALL
+            .iter()
+            .position(|&class| class == self.priority.class)
+            .unwrap_or(0);
+        self.priority.class = IoClass::ALL[(index + 1) % IoClass::ALL.len()];
+    }
+
+    pub fn previous(&mut self) {
+        let index = IoClass::ALL
+            .iter()
+            .position(|&class| class == self.priority.class)
+            .unwrap_or(0);
+        self.priority.class = IoClass::ALL[index.checked_sub(1).unwrap_or(IoClass::ALL.len() - 1)];
+    }
+
+    // Levels only mean something for realtime and best-effort; the others keep theirs for later.
+    pub fn change_level(&mut self, delta: i8) {
+        if self.priority.class.has_level() {
+            self.priority.level = self
+                .priority
+                .level
+                .saturating_add_signed(delta)
+                .clamp(*IO_LEVELS.start(), *IO_LEVELS.end());
         }
     }
+
+    pub fn set_level(&mut self, level: u8) {
+        if self.priority.class.has_level() && IO_LEVELS.contains(&level) {
+            self.priority.level = level;
+        }
+    }
+}
+
+// Every column in display order, shown ones first, so they can be toggled and moved around.
+pub struct ColumnPicker {
+    pub columns: Vec<(Column, bool)>,
+    pub selected: usize,
+}
+
+impl ColumnPicker {
+    pub fn new(shown: &[Column]) -> Self {
+        let hidden = Column::ALL
+            .into_iter()
+            .filter(|column| !shown.contains(column))
+            .map(|column| (column, false));
+        ColumnPicker {
+            columns: shown
+                .iter()
+                .map(|&column| (column, true))
+                .chain(hidden)
+                .collect(),
+            selected: 0,
+        }
+    }
+
+    pub fn next(&mut self) {
+        self.selected = (self.selected + 1) % self.columns.len();
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = self
+            .selected
+            .checked_sub(1)
+            .unwrap_or(self.columns.len() - 1);
+    }
+
+    pub fn toggle(&mut self) {
+        let (_, shown) = &mut self.columns[self.selected];
+        *shown = !*shown;
+    }
+
+    // Swaps the selected column with its neighbour; the selection moves along with it.
+    pub fn move_selected(&mut self, down: bool) {
+        let target = if down {
+            self.selected + 1
+        } else {
+            match self.selected.checked_sub(1) {
+                Some(target) => target,
+                None => return,
+            }
+        };
+        if target < self.columns.len() {
+            self.columns.swap(self.selected, target);
+            self.selected = target;
+        }
+    }
+
+    pub fn shown(&self) -> Vec<Column> {
+        self.columns
+            .iter()
+            .filter(|(_, shown)| *shown)
+            .map(|(column, _)| *column)
+            .collect()
+    }
 }
 
 #[derive(Debug, PartialEq)]
-pub enum AppMode {
-    Normal,
-    Command,
-    Filtering,
+pub enum MessageKind {
+    Info,
+    Error,
+}
+
+pub struct StatusMessage {
+    pub text: String,
+    pub kind: MessageKind,
+    pub shown_at: Instant,
 }
 
 pub struct ProcessItem {
-    pub pid: String,
+    pub pid: u32,
+    pub parent: Option<u32>,
     pub name: String,
-    pub cpu_usage: String,
-    pub memory: String,
+    pub cmd: String,
+    pub user: String,
+    pub status: ProcessStatus,
+    pub threads: Option<usize>,
+    pub nice: Option<i32>,
+    pub io_priority: Option<IoPriority>,
+    pub cpu_usage: f32,
+    pub memory: u64,
+    pub virtual_memory: u64,
+    pub shared_memory: Option<u64>,
+    // Seconds since the epoch.
+    pub start_time: u64,
+    // Seconds since the process started.
+    pub run_time: u64,
+    // Milliseconds of CPU time used so far, across all cores.
+    pub cpu_time: u64,
+    // Bytes per second over the last refresh.
+    pub disk_read: u64,
+    pub disk_write: u64,
+    pub tree: Option<TreeNode>,
+}
+
+// The one-letter codes `ps` uses.
+pub fn state_code(status: ProcessStatus) -> char {
+    match status {
+        ProcessStatus::Run => 'R',
+        ProcessStatus::Sleep => 'S',
+        ProcessStatus::Idle => 'I',
+        ProcessStatus::UninterruptibleDiskSleep => 'D',
+        ProcessStatus::Stop => 'T',
+        ProcessStatus::Tracing => 't',
+        ProcessStatus::Zombie => 'Z',
+        ProcessStatus::Dead => 'X',
+        ProcessStatus::Wakekill => 'K',
+        ProcessStatus::Waking => 'W',
+        ProcessStatus::Parked => 'P',
+        ProcessStatus::LockBlocked => 'L',
+        ProcessStatus::Unknown(_) => '?',
+    }
+}
+
+pub fn state_name(status: ProcessStatus) -> &'static str {
+    match status {
+        ProcessStatus::Run => "running",
+        ProcessStatus::Sleep => "sleeping",
+        ProcessStatus::Idle => "idle",
+        ProcessStatus::UninterruptibleDiskSleep => "disk-sleep",
+        ProcessStatus::Stop => "stopped",
+        ProcessStatus::Tracing => "tracing",
+        ProcessStatus::Zombie => "zombie",
+        ProcessStatus::Dead => "dead",
+        ProcessStatus::Wakekill => "wakekill",
+        ProcessStatus::Waking => "waking",
+        ProcessStatus::Parked => "parked",
+        ProcessStatus::LockBlocked => "lock-blocked",
+        ProcessStatus::Unknown(_) => "unknown",
+    }
+}
+
+pub struct TreeNode {
+    pub depth: usize,
+    pub guides: Vec<bool>,
+    pub is_last: bool,
+    pub has_children: bool,
+    pub collapsed: bool,
+    pub subtree_cpu: f32,
+    pub subtree_memory: u64,
+}
+
+// Where things were drawn on the last frame, so mouse clicks can be mapped back to them.
+#[derive(Default)]
+pub struct ScreenLayout {
+    pub header: Rect,
+    pub rows: Rect,
+    pub columns: Vec<(Column, Rect)>,
+    pub cpu_cores: Rect,
+    pub dialog: Option<Rect>,
+    pub dialog_list: Rect,
+    pub confirm_send: Rect,
+    pub confirm_back: Rect,
+    pub help: Rect,
+    pub details: Rect,
+}
+
+impl ScreenLayout {
+    pub fn column_at(&self, x: u16, y: u16) -> Option<Column> {
+        self.columns
+            .iter()
+            .find(|(_, area)| area.contains(Position { x, y }))
+            .map(|(column, _)| *column)
+    }
+
+    // Index into the process list of the row under the pointer, given the table's scroll offset.
+    pub fn row_at(&self, x: u16, y: u16, offset: usize) -> Option<usize> {
+        self.rows
+            .contains(Position { x, y })
+            .then(|| offset + (y - self.rows.y) as usize)
+    }
 }
 
 pub struct App {
     pub sys: System,
+    pub users: Users,
+    pub groups: Groups,
     pub processes: Vec<ProcessItem>,
+    pub total_processes: usize,
+    // Processes in uninterruptible sleep, filtered out or not; a pile-up means stuck I/O.
+    pub disk_sleeping: usize,
     pub table_state: TableState,
+    pub selected_pid: Option<u32>,
     pub theme: AppTheme,
+    pub themes: ThemeRegistry,
+    pub keymap: Keymap,
+    pub should_quit: bool,
+    pub mouse: bool,
+    pub page_size: usize,
     pub mode: AppMode,
     pub command_buffer: String,
-    pub sort_by: SortBy,
+    pub command_history: CommandHistory,
+    pub sort_by: Column,
+    pub sort_order: SortOrder,
+    // Breaks ties of the main sort column, before falling back to the PID.
+    pub secondary_sort: Option<(Column, SortOrder)>,
     pub filter_query: String,
-    pub cpu_history: Vec<(f64, f64)>,
+    pub filter: Option<Filter>,
+    pub filter_error: Option<String>,
+    pub search_query: String,
+    pub search_origin: Option<u32>,
+    pub pid_filter: Option<HashSet<u32>>,
+    pub user_filter: Option<Uid>,
+    pub refresh_interval: Duration,
+    pub columns: Vec<Column>,
+    pub column_picker: Option<ColumnPicker>,
+    pub proc_extras: HashMap<u32, ProcExtra>,
+    pub sample_period: Duration,
+    last_sample: Option<Instant>,
+    pub panels: Panels,
+    pub history_len: usize,
+    pub cpu_history: History<f32>,
+    pub core_history: Vec<History<f32>>,
+    pub memory_history: MemoryHistory,
     pub tick_count: u64,
     pub original_theme: Option<AppTheme>,
+    pub tree_view: bool,
+    pub collapsed: HashSet<u32>,
+    pub show_subtree_totals: bool,
+    // PIDs of marked processes; they stay marked while the process lives, filtered out or not.
+    pub marked: HashSet<u32>,
+    pub signal_dialog: Option<SignalDialog>,
+    pub tree_signal_order: TreeSignalOrder,
+    pub io_priority_dialog: Option<IoPriorityDialog>,
+    pub throttles: Throttles,
+    pub details_pid: Option<u32>,
+    pub details_scroll: usize,
+    pub help_scroll: usize,
+    pub message: Option<StatusMessage>,
+    pub layout: ScreenLayout,
 }
 
 impl App {
-    pub fn new() -> Self {
+    pub fn new(cli: &Cli) -> Result<Self> {
+        let mut config = Config::load()?;
+        cli.apply(&mut config);
+
+        let themes = ThemeRegistry::load()?;
+        let theme = themes.find(&config.theme).cloned().ok_or_else(|| {
+            anyhow!(
+                "unknown theme `{}`, expected one of: {}",
+                config.theme,
+                themes.names().join(", ")
+            )
+        })?;
+
+        let user_filter = match &cli.user {
+            Some(user) => Some(resolve_user(user)?),
+            None => None,
+        };
+
+        // A broken history file should not keep lazy-top from starting.
+        let (command_history, history_error) = match CommandHistory::load() {
+            Ok(history) => (history, None),
+            Err(err) => (CommandHistory::in_memory(), Some(err)),
+        };
+
+        let filter_query = cli.filter.clone().unwrap_or_default();
+        let filter = parse_filter(&filter_query)
+            .map_err(|err| anyhow!("invalid --filter `{}`: {}", filter_query, err))?;
+
         let mut table_state = TableState::default();
         table_state.select(Some(0));
 
-        App {
+        let mut app = App {
             sys: System::new_all(),
+            users: Users::new_with_refreshed_list(),
+            groups: Groups::new_with_refreshed_list(),
             processes: Vec::new(),
+            total_processes: 0,
+            disk_sleeping: 0,
             table_state,
-            theme: AppTheme::GitHub,
+            selected_pid: None,
+            theme,
+            themes,
+            keymap: Keymap::from_config(&config.keys)?,
+            should_quit: false,
+            mouse: config.mouse,
+            page_size: 10,
             mode: AppMode::Normal,
             command_buffer: String::new(),
-            sort_by: SortBy::Cpu,
-            filter_query: String::new(),
-            cpu_history: Vec::new(),
+            command_history,
+            sort_by: config.sort,
+            sort_order: config
+                .sort_order
+                .unwrap_or_else(|| config.sort.default_order()),
+            secondary_sort: config.secondary_sort.map(|column| {
+                let order = config
+                    .secondary_sort_order
+                    .unwrap_or_else(|| column.default_order());
+                (column, order)
+            }),
+            filter_query,
+            filter,
+            filter_error: None,
+            search_query: String::new(),
+            search_origin: None,
+            pid_filter: cli.pid.as_ref().map(|pids| pids.iter().copied().collect()),
+            user_filter,
+            refresh_interval: config.interval,
+            columns: config.columns,
+            column_picker: None,
+            proc_extras: HashMap::new(),
+            sample_period: config.interval,
+            last_sample: None,
+            panels: config.panels,
+            history_len: config.history_length,
+            cpu_history: History::new(config.history_length),
+            core_history: Vec::new(),
+            memory_history: MemoryHistory::new(config.history_length),
             tick_count: 0,
             original_theme: None,
+            tree_view: cli.tree,
+            collapsed: HashSet::new(),
+            show_subtree_totals: false,
+            marked: HashSet::new(),
+            signal_dialog: None,
+            tree_signal_order: config.tree_signal_order,
+            io_priority_dialog: None,
+            throttles: Throttles::default(),
+            details_pid: None,
+            details_scroll: 0,
+            help_scroll: 0,
+            message: None,
+            layout: ScreenLayout::default(),
+        };
+        if let Some(err) = history_error {
+            app.set_error(format!("Command history not loaded: {:#}", err));
+        }
+        Ok(app)
+    }
+
+    // Parsed here, once per edit, rather than on every refresh. While the text does not parse,
+    // the last good filter stays in effect and the error is shown next to it.
+    pub fn set_filter_query(&mut self, query: String) {
+        match parse_filter(&query) {
+            Ok(filter) => {
+                self.filter = filter;
+                self.filter_error = None;
+            }
+            Err(err) => self.filter_error = Some(err.to_string()),
+        }
+        self.filter_query = query;
+    }
+
+    pub fn start_search(&mut self) {
+        self.search_query.clear();
+        self.search_origin = self.selected_pid;
+        self.mode = AppMode::Search;
+    }
+
+    // Esc puts the selection back where the search started.
+    pub fn cancel_search(&mut self) {
+        self.search_query.clear();
+        if let Some(pid) = self.search_origin.take() {
+            self.select_pid(pid);
+        }
+        self.mode = AppMode::Normal;
+    }
+
+    // Esc in normal mode drops a confirmed search, leaving the selection where it is.
+    pub fn clear_search(&mut self) {
+        self.search_query.clear();
+        self.search_origin = None;
+    }
+
+    // As the query changes, the selection moves to the first match at or after where the search
+    // started.
+    pub fn set_search_query(&mut self, query: String) {
+        self.search_query = query;
+        let start = self
+            .search_origin
+            .and_then(|pid| self.processes.iter().position(|item| item.pid == pid))
+            .unwrap_or(0);
+        if let Some(index) = self.find_match(start, true) {
+            self.select_index(Some(index));
+        }
+    }
+
+    pub fn search_next(&mut self, forward: bool) {
+        if self.search_query.is_empty() {
+            return;
+        }
+        let len = self.processes.len();
+        let start = match self.table_state.selected() {
+            Some(selected) if forward => selected + 1,
+            Some(selected) => (selected + len).saturating_sub(1),
+            None => 0,
+        };
+        match self.find_match(start, forward) {
+            Some(index) => self.select_index(Some(index)),
+            None => self.set_error(format!("No match for '{}'", self.search_query)),
+        }
+    }
+
+    // Case-insensitive substring of the name or PID. ASCII folding keeps byte offsets intact, which
+    // the table relies on to highlight the matched text.
+    pub fn search_matches(&self, item: &ProcessItem) -> bool {
+        !self.search_query.is_empty()
+            && (item
+                .name
+                .to_ascii_lowercase()
+                .contains(&self.search_query.to_ascii_lowercase())
+                || item.pid.to_string().contains(&self.search_query))
+    }
+
+    pub fn search_match_count(&self) -> usize {
+        self.processes
+            .iter()
+            .filter(|item| self.search_matches(item))
+            .count()
+    }
+
+    // Walks the whole list once from `start`, wrapping around.
+    fn find_match(&self, start: usize, forward: bool) -> Option<usize> {
+        let len = self.processes.len();
+        (0..len)
+            .map(|step| {
+                if forward {
+                    (start + step) % len
+                } else {
+                    (start % len + len - step) % len
+                }
+            })
+            .find(|&index| self.search_matches(&self.processes[index]))
+    }
+
+    // Like the signal dialog, the view sticks to the PID it was opened for.
+    pub fn open_details(&mut self) {
+        if let Some(pid) = self.selected_item().map(|item| item.pid) {
+            self.details_pid = Some(pid);
+            self.details_scroll = 0;
+            self.mode = AppMode::Details;
+        }
+    }
+
+    pub fn close_details(&mut self) {
+        self.details_pid = None;
+        self.mode = AppMode::Normal;
+    }
+
+    // Bounded when drawn, like the help popup.
+    pub fn scroll_details(&mut self, delta: isize) {
+        self.details_scroll = self.details_scroll.saturating_add_signed(delta);
+    }
+
+    pub fn open_help(&mut self) {
+        self.help_scroll = 0;
+        self.mode = AppMode::Help;
+    }
+
+    // The upper bound is applied when the popup is drawn, since only then is its height known.
+    pub fn scroll_help(&mut self, delta: isize) {
+        self.help_scroll = self.help_scroll.saturating_add_signed(delta);
+    }
+
+    pub fn set_message(&mut self, text: impl Into<String>) {
+        self.message = Some(StatusMessage {
+            text: text.into(),
+            kind: MessageKind::Info,
+            shown_at: Instant::now(),
+        });
+    }
+
+    pub fn set_error(&mut self, text: impl Into<String>) {
+        self.message = Some(StatusMessage {
+            text: text.into(),
+            kind: MessageKind::Error,
+            shown_at: Instant::now(),
+        });
+    }
+
+    pub fn current_message(&self) -> Option<&StatusMessage> {
+        self.message
+            .as_ref()
+            .filter(|message| message.shown_at.elapsed() < MESSAGE_TIMEOUT)
+    }
+
+    pub fn toggle_cpu_cores(&mut self) {
+        self.panels.cpu_cores = !self.panels.cpu_cores;
+    }
+
+    pub fn toggle_core_heatmap(&mut self) {
+        self.panels.core_heatmap = !self.panels.core_heatmap;
+    }
+
+    pub fn toggle_tree_view(&mut self) {
+        self.tree_view = !self.tree_view;
+    }
+
+    pub fn toggle_subtree_totals(&mut self) {
+        self.show_subtree_totals = !self.show_subtree_totals;
+    }
+
+    pub fn collapse_selected(&mut self) {
+        let Some(item) = self.selected_item() else {
+            return;
+        };
+        let Some(node) = &item.tree else {
+            return;
+        };
+
+        if node.has_children && !node.collapsed {
+            self.collapsed.insert(item.pid);
+        } else if let Some(parent) = item.parent {
+            // Already collapsed or a leaf: jump to the parent like a file tree would.
+            if let Some(index) = self.processes.iter().position(|p| p.pid == parent) {
+                self.select_index(Some(index));
+            }
+        }
+    }
+
+    pub fn expand_selected(&mut self) {
+        if let Some(pid) = self.selected_item().map(|item| item.pid) {
+            self.collapsed.remove(&pid);
         }
     }
 
+    pub fn selected_item(&self) -> Option<&ProcessItem> {
+        self.table_state
+            .selected()
+            .and_then(|selected| self.processes.get(selected))
+    }
+
+    // Steps through the visible columns, starting over from the first one after the last.
     pub fn cycle_sort_coloumn(&mut self) {
-        self.sort_by = self.sort_by.next();
+        let next = self
+            .columns
+            .iter()
+            .position(|&column| column == self.sort_by)
+            .map_or(0, |index| (index + 1) % self.columns.len());
+        self.set_sort(self.columns[next], None);
+    }
+
+    pub fn reverse_sort(&mut self) {
+        self.sort_order = self.sort_order.reversed();
+    }
+
+    // Clicking the current sort column flips the order, any other column starts in its default order.
+    pub fn sort_by_column(&mut self, column: Column) {
+        if self.sort_by == column {
+            self.reverse_sort();
+        } else {
+            self.set_sort(column, None);
+        }
+    }
+
+    // The same for the secondary column; clicking it a third time drops it again.
+    pub fn secondary_sort_by_column(&mut self, column: Column) {
+        if column == self.sort_by {
+            return;
+        }
+        self.secondary_sort = match self.secondary_sort {
+            Some((current, order)) if current == column && order == column.default_order() => {
+                Some((column, order.reversed()))
+            }
+            Some((current, _)) if current == column => None,
+            _ => Some((column, column.default_order())),
+        };
+    }
+
+    // A secondary column equal to the new main one would have no effect, so it is dropped.
+    pub fn set_sort(&mut self, column: Column, order: Option<SortOrder>) {
+        self.sort_by = column;
+        self.sort_order = order.unwrap_or_else(|| column.default_order());
+        if self
+            .secondary_sort
+            .is_some_and(|(secondary, _)| secondary == column)
+        {
+            self.secondary_sort = None;
+        }
     }
 
-    pub fn kill_selected_process(&mut self) {
-        if let Some(selected) = self.table_state.selected() {
-            if let Some(item) = self.processes.get(selected) {
-                if let Ok(pid) = item.pid.parse::<usize>() {
-                    if let Some(process) = self.sys.process(Pid::from(pid)) {
-                        process.kill();
-                    }
+    // "cpu ↓, memory ↓" for the status bar and help.
+    pub fn sort_description(&self) -> String {
+        let mut text = format!("{} {}", self.sort_by.id(), self.sort_order.arrow());
+        if let Some((column, order)) = self.secondary_sort {
+            text.push_str(&format!(", {} {}", column.id(), order.arrow()));
+        }
+        text
+    }
+
+    pub fn open_column_picker(&mut self) {
+        self.column_picker = Some(ColumnPicker::new(&self.columns));
+        self.mode = AppMode::ColumnPicker;
+    }
+
+    pub fn close_column_picker(&mut self) {
+        self.column_picker = None;
+        self.mode = AppMode::Normal;
+    }
+
+    pub fn apply_column_picker(&mut self) {
+        let Some(picker) = self.column_picker.take() else {
+            return;
+        };
+        self.mode = AppMode::Normal;
+        let columns = picker.shown();
+        if columns.is_empty() {
+            self.set_error("At least one column must stay visible");
+            return;
+        }
+        self.set_columns(columns);
+    }
+
+    // Shows `columns` and writes them to the config file so they stick.
+    pub fn set_columns(&mut self, columns: Vec<Column>) {
+        self.columns = columns;
+        let shown: Vec<&str> = self.columns.iter().map(|column| column.id()).collect();
+        match save_columns(&self.columns) {
+            Ok(path) => self.set_message(format!(
+                "Columns: {} (saved to {})",
+                shown.join(" "),
+                path.display()
+            )),
+            Err(err) => self.set_error(format!(
+                "Columns: {} (not saved: {:#})",
+                shown.join(" "),
+                err
+            )),
+        }
+    }
+
+    // What actions apply to: every marked process while there are any, marked ones hidden by the
+    // filter included, otherwise the selected one.
+    pub fn targets(&self) -> Vec<Target> {
+        if self.marked.is_empty() {
+            return self
+                .selected_item()
+                .map(|item| Target {
+                    pid: item.pid,
+                    name: item.name.clone(),
+                })
+                .into_iter()
+                .collect();
+        }
+        let mut pids: Vec<u32> = self.marked.iter().copied().collect();
+        pids.sort_unstable();
+        pids.into_iter()
+            .filter_map(|pid| {
+                Some(Target {
+                    pid,
+                    name: self.process_name(pid)?,
+                })
+            })
+            .collect()
+    }
+
+    // Marks or unmarks the selected process and moves on, so a run of rows takes one key each.
+    pub fn toggle_mark(&mut self) {
+        let Some(pid) = self.selected_item().map(|item| item.pid) else {
+            return;
+        };
+        if !self.marked.remove(&pid) {
+            self.marked.insert(pid);
+        }
+        self.scroll_down(1);
+    }
+
+    // Marks everything the filter currently lets through.
+    pub fn mark_all(&mut self) {
+        let before = self.marked.len();
+        self.marked
+            .extend(self.processes.iter().map(|item| item.pid));
+        self.set_message(format!(
+            "Marked {} processes, {} in total",
+            self.marked.len() - before,
+            self.marked.len()
+        ));
+    }
+
+    pub fn clear_marks(&mut self) {
+        if !self.marked.is_empty() {
+            self.set_message(format!("Cleared {} marks", self.marked.len()));
+            self.marked.clear();
+        }
+    }
+
+    pub fn open_signal_dialog(&mut self) {
+        // The targets are fixed here so a refresh re-sort cannot change them behind the user's back.
+        let targets = self.targets();
+        self.open_signal_dialog_for(targets, None);
+    }
+
+    // Like the signal dialog, for the marked or selected processes and everything below them.
+    pub fn open_tree_signal_dialog(&mut self) {
+        let roots = self.targets();
+        let pids: Vec<u32> = roots.iter().map(|target| target.pid).collect();
+        // lazy-top itself is left out, or it could stop before the rest got the signal.
+        let own_pid = std::process::id();
+        let targets = subtree_pids(&self.sys, &pids, self.tree_signal_order)
+            .into_iter()
+            .filter(|&pid| pid != own_pid)
+            .filter_map(|pid| {
+                Some(Target {
+                    pid,
+                    name: self.process_name(pid)?,
+                })
+            })
+            .collect();
+        let order = self.tree_signal_order;
+        self.open_signal_dialog_for(targets, Some(SubtreeSignal { roots, order }));
+    }
+
+    fn open_signal_dialog_for(&mut self, targets: Vec<Target>, subtree: Option<SubtreeSignal>) {
+        if targets.is_empty() {
+            return;
+        }
+        self.signal_dialog = Some(SignalDialog {
+            targets,
+            subtree,
+            selected: 0,
+            custom_number: String::new(),
+            confirming: false,
+        });
+        self.mode = AppMode::SignalPicker;
+    }
+
+    pub fn close_signal_dialog(&mut self) {
+        self.signal_dialog = None;
+        self.mode = AppMode::Normal;
+    }
+
+    pub fn send_pending_signal(&mut self) {
+        let Some(dialog) = self.signal_dialog.take() else {
+            return;
+        };
+        self.mode = AppMode::Normal;
+
+        let Some(choice) = dialog.choice() else {
+            self.set_error(format!("Invalid signal number '{}'", dialog.custom_number));
+            return;
+        };
+
+        self.signal_processes(&dialog.targets, choice);
+    }
+
+    pub fn signal_process(&mut self, pid: u32, name: &str, choice: SignalChoice) {
+        match send_signal(&self.sys, pid, choice) {
+            Ok(()) => self.set_message(format!("Sent {} to {} ({})", choice.label(), pid, name)),
+            Err(err) => self.set_error(format!(
+                "Failed to send {} to {} ({}): {}",
+                choice.label(),
+                pid,
+                name,
+                err
+            )),
+        }
+    }
+
+    pub fn signal_processes(&mut self, targets: &[Target], choice: SignalChoice) {
+        if let [target] = targets {
+            return self.signal_process(target.pid, &target.name, choice);
+        }
+        // lazy-top itself goes last, so a KILL or STOP that ends or halts it reaches the rest first.
+        let own_pid = std::process::id();
+        let (own, others): (Vec<&Target>, Vec<&Target>) =
+            targets.iter().partition(|target| target.pid == own_pid);
+        let failures = others
+            .into_iter()
+            .chain(own)
+            .filter_map(|target| {
+                let err = send_signal(&self.sys, target.pid, choice).err()?;
+                Some(target.failure(err))
+            })
+            .collect();
+        self.report_bulk(
+            format!("Sent {} to", choice.label()),
+            targets.len(),
+            failures,
+        );
+    }
+
+    // Suspends the targets, or resumes them once every one is suspended. A throttled process
+    // counts as running, and freezing it ends the throttle, which would otherwise wake it again.
+    pub fn toggle_freeze(&mut self) {
+        let mut targets = self.targets();
+        let skipped_self = remove_self(&mut targets);
+        if targets.is_empty() {
+            if skipped_self {
+                self.set_error("lazy-top cannot freeze itself");
+            }
+            return;
+        }
+        let frozen = targets.iter().all(|target| self.is_frozen(target.pid));
+        self.throttles
+            .release(targets.iter().map(|target| target.pid));
+        let signal = if frozen {
+            Signal::Continue
+        } else {
+            Signal::Stop
+        };
+        self.signal_processes(&targets, SignalChoice::Named(signal));
+        if skipped_self {
+            self.note_skipped_self();
+        }
+    }
+
+    // Stopped by a signal, rather than just between two slices of a throttle.
+    pub fn is_frozen(&self, pid: u32) -> bool {
+        self.throttles.limit(pid).is_none()
+            && self
+                .sys
+                .process(Pid::from_u32(pid))
+                .is_some_and(|process| process.status() == ProcessStatus::Stop)
+    }
+
+    // Caps each target at `limit` percent of one core, or lifts the cap when there is no limit.
+    pub fn throttle_processes(&mut self, mut targets: Vec<Target>, limit: Option<f32>) {
+        let Some(limit) = limit else {
+            let released = self
+                .throttles
+                .release(targets.iter().map(|target| target.pid));
+            match targets.as_slice() {
+                [target] if released == 0 => {
+                    self.set_error(format!("{} ({}) is not throttled", target.pid, target.name))
+                }
+                [target] => self.set_message(format!(
+                    "Released the throttle of {} ({})",
+                    target.pid, target.name
+                )),
+                _ => self.set_message(format!("Released {} throttles", released)),
+            }
+            return;
+        };
+
+        let skipped_self = remove_self(&mut targets);
+        if targets.is_empty() {
+            if skipped_self {
+                self.set_error("lazy-top cannot throttle itself");
+            }
+            return;
+        }
+        let failures: Vec<String> = targets
+            .iter()
+            .filter_map(|target| {
+                let err = self.throttles.start(target.pid, limit).err()?;
+                Some(target.failure(err))
+            })
+            .collect();
+        match (targets.as_slice(), failures.as_slice()) {
+            ([target], []) => self.set_message(format!(
+                "Capped the CPU of {} ({}) at {}%",
+                target.pid, target.name, limit
+            )),
+            ([_], [failure]) => self.set_error(format!("Failed to throttle {}", failure)),
+            _ => self.report_bulk(
+                format!("Capped the CPU at {}% for", limit),
+                targets.len(),
+                failures,
+            ),
+        }
+        if skipped_self {
+            self.note_skipped_self();
+        }
+    }
+
+    // Adds to the report of an action that left lazy-top's own process out.
+    fn note_skipped_self(&mut self) {
+        if let Some(message) = &mut self.message {
+            message.text.push_str(" (skipped lazy-top itself)");
+        }
+    }
+
+    pub fn renice_process(&mut self, pid: u32, name: &str, nice: i32) {
+        match set_nice(pid, nice) {
+            Ok(()) => {
+                self.patch_extra(pid, |extra| extra.nice = Some(nice));
+                self.update_processes();
+                self.set_message(format!("Set nice of {} ({}) to {}", pid, name, nice));
+            }
+            Err(err) => self.set_error(format!("Failed to renice {} ({}): {}", pid, name, err)),
+        }
+    }
+
+    pub fn renice_processes(&mut self, targets: &[Target], nice: i32) {
+        if let [target] = targets {
+            return self.renice_process(target.pid, &target.name, nice);
+        }
+        let mut failures = Vec::new();
+        for target in targets {
+            match set_nice(target.pid, nice) {
+                Ok(()) => self.patch_extra(target.pid, |extra| extra.nice = Some(nice)),
+                Err(err) => failures.push(target.failure(err)),
+            }
+        }
+        self.update_processes();
+        self.report_bulk(format!("Set nice to {} for", nice), targets.len(), failures);
+    }
+
+    // One step nicer (lower priority) or less nice for each target, within the range.
+    pub fn adjust_nice(&mut self, delta: i32) {
+        let targets = self.targets();
+        if let [target] = targets.as_slice() {
+            let Some(current) = self.extra(target.pid).nice else {
+                self.set_error(format!(
+                    "Failed to read the nice value of {} ({})",
+                    target.pid, target.name
+                ));
+                return;
+            };
+            let nice = (current + delta).clamp(*NICE_RANGE.start(), *NICE_RANGE.end());
+            if nice == current {
+                self.set_message(format!(
+                    "Nice of {} ({}) is already {}",
+                    target.pid, target.name, nice
+                ));
+                return;
+            }
+            return self.renice_process(target.pid, &target.name, nice);
+        }
+
+        let mut failures = Vec::new();
+        for target in &targets {
+            let result = match self.extra(target.pid).nice {
+                Some(current) => {
+                    let nice = (current + delta).clamp(*NICE_RANGE.start(), *NICE_RANGE.end());
+                    set_nice(target.pid, nice).map(|()| nice)
                 }
+                None => Err(io::Error::other("cannot read the nice value")),
+            };
+            match result {
+                Ok(nice) => self.patch_extra(target.pid, |extra| extra.nice = Some(nice)),
+                Err(err) => failures.push(target.failure(err)),
             }
         }
+        self.update_processes();
+        let verb = if delta > 0 { "Raised" } else { "Lowered" };
+        self.report_bulk(
+            format!("{} the nice value of", verb),
+            targets.len(),
+            failures,
+        );
+    }
+
+    pub fn open_io_priority_dialog(&mut self) {
+        let targets = self.targets();
+        let Some(first) = targets.first() else {
+            return;
+        };
+        // With several targets the dialog starts from the first one's setting.
+        let mut priority = match io_priority(first.pid) {
+            Ok(priority) => priority,
+            Err(err) => {
+                self.set_error(format!(
+                    "Failed to read the I/O priority of {} ({}): {}",
+                    first.pid, first.name, err
+                ));
+                return;
+            }
+        };
+        if !priority.class.has_level() {
+            priority.level = DEFAULT_IO_LEVEL;
+        }
+        self.io_priority_dialog = Some(IoPriorityDialog { targets, priority });
+        self.mode = AppMode::IoPriority;
+    }
+
+    pub fn close_io_priority_dialog(&mut self) {
+        self.io_priority_dialog = None;
+        self.mode = AppMode::Normal;
+    }
+
+    pub fn apply_io_priority_dialog(&mut self) {
+        let Some(dialog) = self.io_priority_dialog.take() else {
+            return;
+        };
+        self.mode = AppMode::Normal;
+        self.set_io_priorities(&dialog.targets, dialog.priority);
+    }
+
+    pub fn set_io_priorities(&mut self, targets: &[Target], priority: IoPriority) {
+        let mut failures = Vec::new();
+        for target in targets {
+            match set_io_priority(target.pid, priority) {
+                Ok(()) => self.patch_extra(target.pid, |extra| extra.io_priority = Some(priority)),
+                Err(err) => failures.push(target.failure(err)),
+            }
+        }
+        self.update_processes();
+
+        match (targets, failures.as_slice()) {
+            ([target], []) => self.set_message(format!(
+                "Set I/O priority of {} ({}) to {}",
+                target.pid,
+                target.name,
+                priority.label()
+            )),
+            ([_], [failure]) => {
+                self.set_error(format!("Failed to set the I/O priority of {}", failure))
+            }
+            _ => self.report_bulk(
+                format!("Set I/O priority {} for", priority.label()),
+                targets.len(),
+                failures,
+            ),
+        }
+    }
+
+    // The procfs extras cost a few system calls per process, so they are only read while a shown
+    // column or a sort key uses them.
+    fn needs_extras(&self) -> bool {
+        let uses_extras =
+            |column: &Column| matches!(column, Column::Nice | Column::IoPriority | Column::Shared);
+        self.columns.iter().any(uses_extras)
+            || uses_extras(&self.sort_by)
+            || self
+                .secondary_sort
+                .is_some_and(|(column, _)| uses_extras(&column))
+    }
+
+    // The extras of one process, from the last sample while they are loaded and read fresh
+    // otherwise, so actions start from what the table shows.
+    fn extra(&self, pid: u32) -> ProcExtra {
+        self.proc_extras
+            .get(&pid)
+            .copied()
+            .unwrap_or_else(|| procfs::read(pid))
+    }
+
+    // Shows a change we just made in the table on the next rebuild rather than the next refresh.
+    // While nothing needs the extras they are not loaded, and there is nothing to patch.
+    fn patch_extra(&mut self, pid: u32, patch: impl FnOnce(&mut ProcExtra)) {
+        if let Some(extra) = self.proc_extras.get_mut(&pid) {
+            patch(extra);
+        }
+    }
+
+    // One status line for an action on several processes: how many it worked for, and why it
+    // failed for the first one it did not.
+    fn report_bulk(&mut self, done: String, total: usize, failures: Vec<String>) {
+        match failures.as_slice() {
+            [] => self.set_message(format!("{} {} processes", done, total)),
+            [first, rest @ ..] => {
+                let more = match rest.len() {
+                    0 => String::new(),
+                    n => format!(" (and {} more)", n),
+                };
+                self.set_error(format!(
+                    "{} {} of {} processes; failed for {}{}",
+                    done,
+                    total - failures.len(),
+                    total,
+                    first,
+                    more
+                ));
+            }
+        }
+    }
+
+    // Name of any running process, whether or not it is currently listed.
+    pub fn process_name(&self, pid: u32) -> Option<String> {
+        self.sys
+            .process(Pid::from_u32(pid))
+            .map(|process| process.name().to_string_lossy().into_owned())
+    }
+
+    pub fn select_pid(&mut self, pid: u32) -> bool {
+        match self.processes.iter().position(|item| item.pid == pid) {
+            Some(index) => {
+                self.select_index(Some(index));
+                true
+            }
+            None => false,
+        }
     }
 
     pub fn next(&mut self) {
@@ -216,7 +1270,7 @@ impl App {
             }
             None => 0,
         };
-        self.table_state.select(Some(i));
+        self.select_index(Some(i));
     }
 
     pub fn previous(&mut self) {
@@ -231,106 +1285,275 @@ impl App {
             None => 0,
         };
 
-        self.table_state.select(Some(i));
+        self.select_index(Some(i));
+    }
+
+    pub fn page_down(&mut self) {
+        self.scroll_down(self.page_size);
+    }
+
+    pub fn page_up(&mut self) {
+        self.scroll_up(self.page_size);
+    }
+
+    pub fn scroll_down(&mut self, rows: usize) {
+        let last = self.processes.len().saturating_sub(1);
+        let i = self.table_state.selected().unwrap_or(0) + rows;
+        self.select_index(Some(i.min(last)));
+    }
+
+    pub fn scroll_up(&mut self, rows: usize) {
+        let i = self.table_state.selected().unwrap_or(0);
+        self.select_index(Some(i.saturating_sub(rows)));
+    }
+
+    pub fn select_row(&mut self, index: usize) {
+        if index < self.processes.len() {
+            self.select_index(Some(index));
+        }
+    }
+
+    pub fn select_first(&mut self) {
+        self.select_index(Some(0));
+    }
+
+    pub fn select_last(&mut self) {
+        self.select_index(Some(self.processes.len().saturating_sub(1)));
     }
 
     pub fn refresh(&mut self) {
         self.sys.refresh_all();
+        // Disk usage is reported per refresh, so rates need the real time between samples.
+        let now = Instant::now();
+        if let Some(last) = self.last_sample.replace(now) {
+            self.sample_period = now - last;
+        }
+
+        // A PID that goes away may come back as another process, which should not arrive marked.
+        let processes = self.sys.processes();
+        self.marked
+            .retain(|&pid| processes.contains_key(&Pid::from_u32(pid)));
+        self.disk_sleeping = processes
+            .values()
+            .filter(|process| process.status() == ProcessStatus::UninterruptibleDiskSleep)
+            .count();
+        // A zombie cannot be stopped any more and its PID is about to be free for reuse.
+        self.throttles.retain(|pid| {
+            processes
+                .get(&Pid::from_u32(pid))
+                .is_some_and(|process| process.status() != ProcessStatus::Zombie)
+        });
 
-        let cpu_usage = self.sys.global_cpu_usage() as f64;
-        self.cpu_history.push((self.tick_count as f64, cpu_usage));
+        // Re-read on the next rebuild, from this sample.
+        self.proc_extras.clear();
 
-        if self.cpu_history.len() > 100 {
-            self.cpu_history.remove(0);
+        self.cpu_history.push(self.sys.global_cpu_usage());
+
+        let cpus = self.sys.cpus();
+        let history_len = self.history_len;
+        self.core_history
+            .resize_with(cpus.len(), || History::new(history_len));
+        for (history, cpu) in self.core_history.iter_mut().zip(cpus) {
+            history.push(cpu.cpu_usage());
         }
+
+        self.memory_history.record(&self.sys);
         self.tick_count += 1;
+        // Accounts change rarely; re-reading them every tick would be wasted work.
+        if self.tick_count.is_multiple_of(USERS_REFRESH_TICKS) {
+            self.users.refresh();
+            self.groups.refresh();
+        }
 
+        self.update_processes();
+    }
+
+    // Rebuilds the process list from the last sample, e.g. after the filter, sort or tree changes.
+    pub fn update_processes(&mut self) {
+        if self.proc_extras.is_empty() && self.needs_extras() {
+            self.proc_extras = self
+                .sys
+                .processes()
+                .keys()
+                .map(|pid| (pid.as_u32(), procfs::read(pid.as_u32())))
+                .collect();
+        }
+        self.total_processes = self.sys.processes().len();
         let mut processes: Vec<ProcessItem> = self
             .sys
             .processes()
             .iter()
-            .map(|(pid, process)| ProcessItem {
-                pid: pid.to_string(),
-                name: process.name().to_string_lossy().into_owned(),
-                cpu_usage: format!("{:.2}%", process.cpu_usage()),
-                memory: format!("{:.2} MB", process.memory() as f64 / 1024.0 / 1024.0),
+            .filter(|(pid, process)| {
+                self.pid_filter
+                    .as_ref()
+                    .is_none_or(|pids| pids.contains(&pid.as_u32()))
+                    && self
+                        .user_filter
+                        .as_ref()
+                        .is_none_or(|uid| process.user_id() == Some(uid))
+            })
+            .map(|(pid, process)| {
+                let extra = self
+                    .proc_extras
+                    .get(&pid.as_u32())
+                    .copied()
+                    .unwrap_or_default();
+                let disk = process.disk_usage();
+                let per_second = |bytes: u64| {
+                    (bytes as f64 / self.sample_period.as_secs_f64().max(0.001)) as u64
+                };
+                ProcessItem {
+                    pid: pid.as_u32(),
+                    parent: process.parent().map(Pid::as_u32),
+                    name: process.name().to_string_lossy().into_owned(),
+                    cmd: process
+                        .cmd()
+                        .iter()
+                        .map(|arg| arg.to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    user: process
+                        .user_id()
+                        .map(|uid| match self.users.get_user_by_id(uid) {
+                            Some(user) => user.name().to_string(),
+                            None => uid.to_string(),
+                        })
+                        .unwrap_or_default(),
+                    status: process.status(),
+                    // sysinfo lists the other threads only; threads themselves have none.
+                    threads: process.tasks().map(|tasks| tasks.len() + 1),
+                    nice: extra.nice,
+                    io_priority: extra.io_priority,
+                    cpu_usage: process.cpu_usage(),
+                    memory: process.memory(),
+                    virtual_memory: process.virtual_memory(),
+                    shared_memory: extra.shared_memory,
+                    start_time: process.start_time(),
+                    run_time: process.run_time(),
+                    cpu_time: process.accumulated_cpu_time(),
+                    disk_read: per_second(disk.read_bytes),
+                    disk_write: per_second(disk.written_bytes),
+                    tree: None,
+                }
             })
             .collect();
 
-        if !self.filter_query.is_empty() {
-            let query = self.filter_query.to_lowercase();
-            processes.retain(|p| p.name.to_lowercase().contains(&query));
+        if let Some(filter) = &self.filter {
+            processes.retain(|p| filter.matches(p));
         }
 
+        // The PID comes last so equal rows keep their place from one refresh to the next.
         processes.sort_by(|a, b| {
-            match self.sort_by {
-                SortBy::Pid => {
-                    let pid_a = a.pid.parse::<usize>().unwrap_or(0);
-                    let pid_b = b.pid.parse::<usize>().unwrap_or(0);
-                    pid_a.cmp(&pid_b)
-                }
-                SortBy::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
-                SortBy::Cpu => {
-                    let cpu_a = a
-                        .cpu_usage
-                        .trim_end_matches('%')
-                        .parse::<f32>()
-                        .unwrap_or(0.0);
-                    let cpu_b = b
-                        .cpu_usage
-                        .trim_end_matches('%')
-                        .parse::<f32>()
-                        .unwrap_or(0.0);
-                    cpu_b.partial_cmp(&cpu_a).unwrap_or(Ordering::Equal) // Note: Higher CPU is better
-                }
-                SortBy::Memory => {
-                    let mem_a = a
-                        .memory
-                        .split_whitespace()
-                        .next()
-                        .unwrap_or("0")
-                        .parse::<f32>()
-                        .unwrap_or(0.0);
-                    let mem_b = b
-                        .memory
-                        .split_whitespace()
-                        .next()
-                        .unwrap_or("0")
-                        .parse::<f32>()
-                        .unwrap_or(0.0);
-                    mem_b.partial_cmp(&mem_a).unwrap_or(Ordering::Equal) // Note: Higher Memory is "greater"
-                }
-            }
+            self.sort_order
+                .apply(self.sort_by.compare(a, b))
+                .then_with(|| match self.secondary_sort {
+                    Some((column, order)) => order.apply(column.compare(a, b)),
+                    None => Ordering::Equal,
+                })
+                .then_with(|| a.pid.cmp(&b.pid))
         });
 
+        if self.tree_view {
+            processes = build_tree(processes, &self.collapsed);
+        }
+
+        // Remember who sat around the selection so we can land next to it if it disappears.
+        let selected_index = self.table_state.selected();
+        let neighbours: Vec<u32> = selected_index
+            .map(|i| {
+                [i.checked_add(1), i.checked_sub(1)]
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|j| self.processes.get(j).map(|p| p.pid))
+                    .collect()
+            })
+            .unwrap_or_default();
+
         self.processes = processes;
 
-        // Ensure selection is not out of bounds
-        if self.table_state.selected().is_some()
-            && self.table_state.selected().unwrap() >= self.processes.len()
-        {
-            self.table_state
-                .select(Some(self.processes.len().saturating_sub(1)));
-        }
+        let position = |pid: u32| self.processes.iter().position(|p| p.pid == pid);
+        let index = self
+            .selected_pid
+            .and_then(position)
+            .or_else(|| neighbours.into_iter().find_map(position))
+            .or_else(|| {
+                // Nothing familiar is left: keep the cursor where it was, within bounds.
+                let last = self.processes.len().checked_sub(1)?;
+                Some(selected_index.unwrap_or(0).min(last))
+            });
+        self.select_index(index);
+    }
+
+    fn select_index(&mut self, index: Option<usize>) {
+        self.table_state.select(index);
+        self.selected_pid = index
+            .and_then(|i| self.processes.get(i))
+            .map(|item| item.pid);
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum SortBy {
-    Pid,
-    Name,
-    Cpu,
-    Memory,
+fn parse_filter(query: &str) -> Result<Option<Filter>> {
+    if query.trim().is_empty() {
+        Ok(None)
+    } else {
+        Filter::parse(query).map(Some)
+    }
+}
+
+// Accepts a user name or a numeric UID.
+fn resolve_user(user: &str) -> Result<Uid> {
+    let users = Users::new_with_refreshed_list();
+    if let Some(found) = users.iter().find(|u| u.name() == user) {
+        return Ok(found.id().clone());
+    }
+    user.parse::<Uid>()
+        .map_err(|_| anyhow!("unknown user `{}`", user))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum SortOrder {
+    #[serde(rename = "asc", alias = "ascending")]
+    Ascending,
+    #[serde(rename = "desc", alias = "descending")]
+    Descending,
 }
 
-impl SortBy {
-    pub fn next(self) -> Self {
+impl SortOrder {
+    pub fn name(self) -> &'static str {
         match self {
-            SortBy::Pid => Self::Name,
-            SortBy::Name => SortBy::Cpu,
-            SortBy::Cpu => SortBy::Memory,
-            SortBy::Memory => SortBy::Pid,
+            SortOrder::Ascending => "asc",
+            SortOrder::Descending => "desc",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SortOrder> {
+        match name.to_lowercase().as_str() {
+            "asc" | "ascending" => Some(SortOrder::Ascending),
+            "desc" | "descending" => Some(SortOrder::Descending),
+            _ => None,
+        }
+    }
+
+    pub fn arrow(self) -> &'static str {
+        match self {
+            SortOrder::Ascending => "↑",
+            SortOrder::Descending => "↓",
+        }
+    }
+
+    pub fn reversed(self) -> SortOrder {
+        match self {
+            SortOrder::Ascending => SortOrder::Descending,
+            SortOrder::Descending => SortOrder::Ascending,
+        }
+    }
+
+    pub fn apply(self, ordering: Ordering) -> Ordering {
+        match self {
+            SortOrder::Ascending => ordering,
+            SortOrder::Descending => ordering.reverse(),
         }
     }
 }
+
 //-----------------------------------------------------------------------------------------------------------------------------------------------
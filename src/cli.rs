@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use clap::Parser;
+
+use crate::columns::Column;
+use crate::config::{Config, MAX_INTERVAL, MIN_INTERVAL, format_duration, parse_duration};
+
+//-----------------------------------------------------------------------------------------------------------------
+
+// Flags override the config file for this session only.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Color theme to start with (built-in or from the themes directory)
+    #[arg(long)]
+    pub theme: Option<String>,
+
+    /// Refresh interval, e.g. "500ms" or "2s"
+    #[arg(long, value_parser = parse_interval)]
+    pub interval: Option<Duration>,
+
+    /// Column to sort by, e.g. pid, name, cpu, memory, user or start
+    #[arg(long, value_parser = parse_sort)]
+    pub sort: Option<Column>,
+
+    /// Only show processes matching a filter expression, e.g. "firefox" or "cpu>5 and
+    /// user=root"; see Filtering Mode in the README
+    #[arg(long)]
+    pub filter: Option<String>,
+
+    /// Only watch these PIDs (comma-separated)
+    #[arg(long, value_delimiter = ',', num_args = 1..)]
+    pub pid: Option<Vec<u32>>,
+
+    /// Only show processes owned by this user name or UID
+    #[arg(long)]
+    pub user: Option<String>,
+
+    /// Start in process tree view
+    #[arg(long)]
+    pub tree: bool,
+
+    /// Leave the mouse to the terminal (no clicks or wheel scrolling)
+    #[arg(long)]
+    pub no_mouse: bool,
+}
+
+impl Cli {
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(theme) = &self.theme {
+            config.theme = theme.clone();
+        }
+        if let Some(interval) = self.interval {
+            config.interval = interval;
+        }
+        if let Some(sort) = self.sort {
+            config.sort = sort;
+            config.sort_order = None;
+            if config.secondary_sort == Some(sort) {
+                config.secondary_sort = None;
+            }
+        }
+        if self.no_mouse {
+            config.mouse = false;
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------------------------------------------
+
+fn parse_interval(s: &str) -> Result<Duration, String> {
+    match parse_duration(s) {
+        Some(interval) if interval < MIN_INTERVAL => {
+            Err(format!("must be at least {}ms", MIN_INTERVAL.as_millis()))
+        }
+        Some(interval) if interval > MAX_INTERVAL => {
+            Err(format!("must be at most {}", format_duration(MAX_INTERVAL)))
+        }
+        Some(interval) => Ok(interval),
+        None => Err("expected a duration such as \"250ms\" or \"1s\"".to_string()),
+    }
+}
+
+fn parse_sort(s: &str) -> Result<Column, String> {
+    Column::from_id(s).ok_or_else(|| {
+        format!(
+            "expected one of: {}",
+            Column::ALL.map(|column| column.id()).join(", ")
+        )
+    })
+}
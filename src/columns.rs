@@ -0,0 +1,344 @@
+use std::cmp::Ordering;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ratatui::layout::{Alignment, Constraint};
+use serde::Deserialize;
+
+use crate::app::{ProcessItem, SortOrder, state_code};
+
+//-----------------------------------------------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    Pid,
+    Ppid,
+    User,
+    Name,
+    State,
+    Threads,
+    Nice,
+    #[serde(rename = "io")]
+    IoPriority,
+    Cpu,
+    #[serde(alias = "mem", alias = "rss")]
+    Memory,
+    #[serde(rename = "virt")]
+    Virtual,
+    #[serde(rename = "shr")]
+    Shared,
+    Start,
+    #[serde(alias = "etime")]
+    Elapsed,
+    #[serde(rename = "time")]
+    CpuTime,
+    #[serde(rename = "cmd")]
+    Command,
+    #[serde(rename = "read")]
+    DiskRead,
+    #[serde(rename = "write")]
+    DiskWrite,
+}
+
+impl Column {
+    pub const ALL: [Column; 18] = [
+        Column::Pid,
+        Column::Ppid,
+        Column::User,
+        Column::Name,
+        Column::State,
+        Column::Threads,
+        Column::Nice,
+        Column::IoPriority,
+        Column::Cpu,
+        Column::Memory,
+        Column::Virtual,
+        Column::Shared,
+        Column::Start,
+        Column::Elapsed,
+        Column::CpuTime,
+        Column::Command,
+        Column::DiskRead,
+        Column::DiskWrite,
+    ];
+
+    // The name used for the column in the config file and in commands.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Column::Pid => "pid",
+            Column::Ppid => "ppid",
+            Column::User => "user",
+            Column::Name => "name",
+            Column::State => "state",
+            Column::Threads => "threads",
+            Column::Nice => "nice",
+            Column::IoPriority => "io",
+            Column::Cpu => "cpu",
+            Column::Memory => "memory",
+            Column::Virtual => "virt",
+            Column::Shared => "shr",
+            Column::Start => "start",
+            Column::Elapsed => "elapsed",
+            Column::CpuTime => "time",
+            Column::Command => "cmd",
+            Column::DiskRead => "read",
+            Column::DiskWrite => "write",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Column> {
+        match id.to_lowercase().as_str() {
+            "mem" | "rss" => Some(Column::Memory),
+            "etime" => Some(Column::Elapsed),
+            id => Column::ALL.into_iter().find(|column| column.id() == id),
+        }
+    }
+
+    pub fn default_set() -> Vec<Column> {
+        vec![Column::Pid, Column::Name, Column::Cpu, Column::Memory]
+    }
+
+    pub fn header(&self) -> &'static str {
+        match self {
+            Column::Pid => "PID",
+            Column::Ppid => "PPID",
+            Column::User => "User",
+            Column::Name => "Name",
+            Column::State => "S",
+            Column::Threads => "Thr",
+            Column::Nice => "NI",
+            Column::IoPriority => "IO",
+            Column::Cpu => "CPU %",
+            Column::Memory => "Memory",
+            Column::Virtual => "VIRT",
+            Column::Shared => "SHR",
+            Column::Start => "Start",
+            Column::Elapsed => "Elapsed",
+            Column::CpuTime => "CPU Time",
+            Column::Command => "Command",
+            Column::DiskRead => "Read/s",
+            Column::DiskWrite => "Write/s",
+        }
+    }
+
+    // What the column shows, for the column picker.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Column::Pid => "Process ID",
+            Column::Ppid => "Parent process ID",
+            Column::User => "Owner",
+            Column::Name => "Process name",
+            Column::State => "State (R, S, D, Z, T, ...)",
+            Column::Threads => "Number of threads",
+            Column::Nice => "Nice value",
+            Column::IoPriority => "I/O scheduling class and level",
+            Column::Cpu => "CPU usage",
+            Column::Memory => "Resident memory (RSS)",
+            Column::Virtual => "Virtual memory",
+            Column::Shared => "Shared memory",
+            Column::Start => "Start time",
+            Column::Elapsed => "Time since the process started",
+            Column::CpuTime => "CPU time used so far",
+            Column::Command => "Full command line",
+            Column::DiskRead => "Disk read rate",
+            Column::DiskWrite => "Disk write rate",
+        }
+    }
+
+    // The sort key. Text compares case-insensitively; missing values sort before any value.
+    pub fn compare(&self, a: &ProcessItem, b: &ProcessItem) -> Ordering {
+        let text = |a: &str, b: &str| a.to_lowercase().cmp(&b.to_lowercase());
+        match self {
+            Column::Pid => a.pid.cmp(&b.pid),
+            Column::Ppid => a.parent.cmp(&b.parent),
+            Column::User => text(&a.user, &b.user),
+            Column::Name => text(&a.name, &b.name),
+            Column::State => state_code(a.status).cmp(&state_code(b.status)),
+            Column::Threads => a.threads.cmp(&b.threads),
+            Column::Nice => a.nice.cmp(&b.nice),
+            Column::IoPriority => a.io_priority.cmp(&b.io_priority),
+            Column::Cpu => a.cpu_usage.total_cmp(&b.cpu_usage),
+            Column::Memory => a.memory.cmp(&b.memory),
+            Column::Virtual => a.virtual_memory.cmp(&b.virtual_memory),
+            Column::Shared => a.shared_memory.cmp(&b.shared_memory),
+            Column::Start => a.start_time.cmp(&b.start_time),
+            Column::Elapsed => a.run_time.cmp(&b.run_time),
+            Column::CpuTime => a.cpu_time.cmp(&b.cpu_time),
+            Column::Command => text(&a.cmd, &b.cmd),
+            Column::DiskRead => a.disk_read.cmp(&b.disk_read),
+            Column::DiskWrite => a.disk_write.cmp(&b.disk_write),
+        }
+    }
+
+    // Amounts read best biggest-first, and start times newest-first; text, IDs and nice values
+    // read best in natural order.
+    pub fn default_order(&self) -> SortOrder {
+        match self {
+            Column::Pid
+            | Column::Ppid
+            | Column::User
+            | Column::Name
+            | Column::State
+            | Column::Nice
+            | Column::IoPriority
+            | Column::Command => SortOrder::Ascending,
+            _ => SortOrder::Descending,
+        }
+    }
+
+    // Text columns take what is left of the row, everything else has a fixed width.
+    pub fn width(&self) -> Constraint {
+        match self {
+            Column::Pid => Constraint::Length(10),
+            Column::Ppid => Constraint::Length(8),
+            Column::User => Constraint::Length(10),
+            Column::Name => Constraint::Min(20),
+            Column::State => Constraint::Length(1),
+            Column::Threads => Constraint::Length(4),
+            Column::Nice => Constraint::Length(3),
+            Column::IoPriority => Constraint::Length(4),
+            Column::Cpu => Constraint::Length(10),
+            Column::Memory => Constraint::Length(12),
+            Column::Virtual | Column::Shared => Constraint::Length(9),
+            Column::Start => Constraint::Length(5),
+            Column::Elapsed => Constraint::Length(11),
+            Column::CpuTime => Constraint::Length(9),
+            Column::Command => Constraint::Min(20),
+            Column::DiskRead | Column::DiskWrite => Constraint::Length(9),
+        }
+    }
+
+    pub fn alignment(&self) -> Alignment {
+        match self {
+            Column::User | Column::Name | Column::State | Column::IoPriority | Column::Command => {
+                Alignment::Left
+            }
+            _ => Alignment::Right,
+        }
+    }
+
+    // The cell text. CPU and memory show the subtree totals in tree view when `totals` is set;
+    // the tree prefix and search highlighting are added by the table.
+    pub fn format(&self, item: &ProcessItem, totals: bool) -> String {
+        let (cpu_usage, memory) = match &item.tree {
+            Some(node) if totals => (node.subtree_cpu, node.subtree_memory),
+            _ => (item.cpu_usage, item.memory),
+        };
+        let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+        match self {
+            Column::Pid => item.pid.to_string(),
+            Column::Ppid => or_dash(item.parent.map(|pid| pid.to_string())),
+            Column::User => item.user.clone(),
+            Column::Name => item.name.clone(),
+            Column::State => state_code(item.status).to_string(),
+            Column::Threads => or_dash(item.threads.map(|threads| threads.to_string())),
+            Column::Nice => or_dash(item.nice.map(|nice| nice.to_string())),
+            Column::IoPriority => or_dash(item.io_priority.map(|priority| priority.label())),
+            Column::Cpu => format!("{:.2}%", cpu_usage),
+            Column::Memory => format_memory(memory),
+            Column::Virtual => format_bytes(item.virtual_memory),
+            Column::Shared => or_dash(item.shared_memory.map(format_bytes)),
+            Column::Start => format_start_time(item.start_time),
+            Column::Elapsed => format_elapsed(item.run_time),
+            Column::CpuTime => format_cpu_time(item.cpu_time),
+            Column::Command => item.cmd.clone(),
+            Column::DiskRead => format_bytes(item.disk_read),
+            Column::DiskWrite => format_bytes(item.disk_write),
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------------------------------------------
+
+pub fn format_memory(bytes: u64) -> String {
+    format!("{:.2} MB", bytes as f64 / 1024.0 / 1024.0)
+}
+
+// Scaled to the largest unit that keeps the number above one, e.g. "512 B" or "1.4 GB".
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+// "M:SS.hh" like top's TIME+, switching to "H:MM:SS" past the hour.
+pub fn format_cpu_time(millis: u64) -> String {
+    let secs = millis / 1000;
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}.{:02}", secs / 60, secs % 60, millis % 1000 / 10)
+    }
+}
+
+// "2d 03:04:05", or "03:04:05" under a day.
+pub fn format_elapsed(secs: u64) -> String {
+    let clock = format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60
+    );
+    match secs / 86400 {
+        0 => clock,
+        days => format!("{}d {}", days, clock),
+    }
+}
+
+// Like `ps`: the time of day for processes started in the last 24 hours, the date otherwise.
+pub fn format_start_time(start: u64) -> String {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    match local_time(start) {
+        Some(time) if now.saturating_sub(start) < 24 * 60 * 60 => {
+            format!("{:02}:{:02}", time.hour, time.minute)
+        }
+        Some(time) => format!("{}{:02}", MONTHS[time.month % 12], time.day),
+        None => "-".to_string(),
+    }
+}
+
+pub struct LocalTime {
+    pub year: i32,
+    // Zero-based, January is 0.
+    pub month: usize,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+#[cfg(unix)]
+pub fn local_time(secs: u64) -> Option<LocalTime> {
+    let time = libc::time_t::try_from(secs).ok()?;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return None;
+    }
+    Some(LocalTime {
+        year: tm.tm_year + 1900,
+        month: tm.tm_mon as usize,
+        day: tm.tm_mday as u32,
+        hour: tm.tm_hour as u32,
+        minute: tm.tm_min as u32,
+        second: tm.tm_sec as u32,
+    })
+}
+
+#[cfg(not(unix))]
+pub fn local_time(_secs: u64) -> Option<LocalTime> {
+    None
+}
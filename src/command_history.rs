@@ -0,0 +1,178 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+
+use crate::config::data_dir;
+
+const MAX_ENTRIES: usize = 1000;
+
+//-----------------------------------------------------------------------------------------------------------------
+
+pub struct HistorySearch {
+    pub query: String,
+    // Index of the entry currently matched, searching from the newest backwards.
+    pub matched: Option<usize>,
+}
+
+// Commands run in command mode, oldest first, mirrored to `command_history` in the data dir.
+pub struct CommandHistory {
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+    position: Option<usize>,
+    draft: String,
+    pub search: Option<HistorySearch>,
+}
+
+impl CommandHistory {
+    // Used when the file cannot be read, so the session still has a history of its own.
+    pub fn in_memory() -> Self {
+        CommandHistory {
+            entries: Vec::new(),
+            path: None,
+            position: None,
+            draft: String::new(),
+            search: None,
+        }
+    }
+
+    pub fn load() -> Result<Self> {
+        let mut history = CommandHistory {
+            path: data_dir().map(|dir| dir.join("command_history")),
+            ..Self::in_memory()
+        };
+        let Some(path) = &history.path else {
+            return Ok(history);
+        };
+
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(history),
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read {}", path.display()));
+            }
+        };
+        history.entries = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(String::from)
+            .collect();
+
+        // The file is only appended to while running, so it is trimmed back here.
+        if history.entries.len() > MAX_ENTRIES {
+            history.entries.drain(..history.entries.len() - MAX_ENTRIES);
+            fs::write(path, history.entries.join("\n") + "\n")
+                .with_context(|| format!("failed to write {}", path.display()))?;
+        }
+        Ok(history)
+    }
+
+    // Adds a command unless it repeats the previous one, and appends it to the file.
+    pub fn record(&mut self, line: &str) -> Result<()> {
+        self.reset();
+        let line = line.trim();
+        if line.is_empty() || self.entries.last().is_some_and(|last| last == line) {
+            return Ok(());
+        }
+        self.entries.push(line.to_string());
+
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        writeln!(file, "{}", line).with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    pub fn reset(&mut self) {
+        self.position = None;
+        self.draft.clear();
+        self.search = None;
+    }
+
+    // Steps back through older commands; the line being typed is kept to come back to.
+    pub fn previous(&mut self, current: &str) -> Option<&str> {
+        let position = match self.position {
+            None => {
+                self.draft = current.to_string();
+                self.entries.len().checked_sub(1)?
+            }
+            Some(position) => position.saturating_sub(1),
+        };
+        self.position = Some(position);
+        self.entries.get(position).map(String::as_str)
+    }
+
+    pub fn next(&mut self) -> Option<&str> {
+        let position = self.position?;
+        if position + 1 < self.entries.len() {
+            self.position = Some(position + 1);
+            self.entries.get(position + 1).map(String::as_str)
+        } else {
+            self.position = None;
+            Some(self.draft.as_str())
+        }
+    }
+
+    //-------------------------------------------------------------------------------------------------------------
+
+    pub fn start_search(&mut self) {
+        self.search = Some(HistorySearch {
+            query: String::new(),
+            matched: None,
+        });
+    }
+
+    pub fn search_push(&mut self, c: char) {
+        if let Some(search) = &mut self.search {
+            search.query.push(c);
+        }
+        self.find_match(self.entries.len());
+    }
+
+    pub fn search_pop(&mut self) {
+        if let Some(search) = &mut self.search {
+            search.query.pop();
+        }
+        self.find_match(self.entries.len());
+    }
+
+    // Ctrl-R again: the next older entry that matches.
+    pub fn search_older(&mut self) {
+        let before = match self.search.as_ref().and_then(|search| search.matched) {
+            Some(index) => index,
+            None => self.entries.len(),
+        };
+        self.find_match(before);
+    }
+
+    pub fn search_match(&self) -> Option<&str> {
+        let index = self.search.as_ref()?.matched?;
+        self.entries.get(index).map(String::as_str)
+    }
+
+    // Keeps the current match when nothing older matches, like a shell does.
+    fn find_match(&mut self, before: usize) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        if search.query.is_empty() {
+            search.matched = None;
+            return;
+        }
+        let found = self.entries[..before.min(self.entries.len())]
+            .iter()
+            .rposition(|entry| entry.contains(&search.query));
+        if found.is_some() || before == self.entries.len() {
+            search.matched = found;
+        }
+    }
+}
@@ -0,0 +1,746 @@
+use std::env;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow, bail};
+
+use crate::app::{App, SortOrder, Target};
+use crate::columns::Column;
+use crate::config::{MAX_INTERVAL, MIN_INTERVAL, format_duration, parse_duration};
+use crate::export::export;
+use crate::filter::Filter;
+use crate::priority::{DEFAULT_IO_LEVEL, IO_LEVELS, IoClass, IoPriority, NICE_RANGE};
+use crate::signals::{SIGNALS, SignalChoice, parse_signal};
+
+//-----------------------------------------------------------------------------------------------------------------
+
+pub struct Command {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub args: &'static str,
+    pub description: &'static str,
+    run: fn(&mut App, &str) -> Result<()>,
+    // Candidates for the argument at the given position, given the word typed so far.
+    complete: fn(&App, usize, &str) -> Vec<String>,
+}
+
+pub const COMMANDS: [Command; 14] = [
+    Command {
+        name: "sort",
+        aliases: &[],
+        args: "<col> [asc|desc] [then <col> [asc|desc]]",
+        description: "Sort by a column, optionally breaking ties by a second one",
+        run: run_sort,
+        complete: complete_sort,
+    },
+    Command {
+        name: "filter",
+        aliases: &[],
+        args: "[expr]",
+        description: "Filter with an expression (cpu>5 and user=root), or clear it",
+        run: run_filter,
+        complete: complete_nothing,
+    },
+    Command {
+        name: "kill",
+        aliases: &[],
+        args: "<pid> [sig]",
+        description: "Send a signal (default TERM) to a process",
+        run: run_kill,
+        complete: complete_kill,
+    },
+    Command {
+        name: "renice",
+        aliases: &[],
+        args: "[pid] <nice>",
+        description: "Set the nice value (-20..19) of a process, or the marked or selected ones",
+        run: run_renice,
+        complete: complete_pid,
+    },
+    Command {
+        name: "ionice",
+        aliases: &[],
+        args: "[pid] <class> [level]",
+        description: "Set the I/O class (none, realtime, best-effort, idle) and level (0..7)",
+        run: run_ionice,
+        complete: complete_ionice,
+    },
+    Command {
+        name: "throttle",
+        aliases: &[],
+        args: "[pid] <percent|off>",
+        description: "Cap the CPU usage of a process, or the marked or selected ones",
+        run: run_throttle,
+        complete: complete_throttle,
+    },
+    Command {
+        name: "interval",
+        aliases: &[],
+        args: "<duration>",
+        description: "Set the refresh interval, e.g. 500ms or 2s",
+        run: run_interval,
+        complete: complete_nothing,
+    },
+    Command {
+        name: "goto",
+        aliases: &[],
+        args: "<pid>",
+        description: "Select the process with this PID",
+        run: run_goto,
+        complete: complete_pid,
+    },
+    Command {
+        name: "columns",
+        aliases: &[],
+        args: "[col...|+col|-col|move col pos]",
+        description: "Pick the columns, set them, add/remove or move one; saved to config",
+        run: run_columns,
+        complete: complete_columns,
+    },
+    Command {
+        name: "export",
+        aliases: &[],
+        args: "<path>",
+        description: "Write the visible (or marked) processes to a CSV or .json file",
+        run: run_export,
+        complete: complete_path,
+    },
+    Command {
+        name: "theme",
+        aliases: &[],
+        args: "<name>",
+        description: "Switch to a theme (Tab previews)",
+        run: run_theme,
+        complete: complete_theme,
+    },
+    Command {
+        name: "tree",
+        aliases: &[],
+        args: "[on|off]",
+        description: "Toggle the tree view, or set it",
+        run: run_tree,
+        complete: complete_tree,
+    },
+    Command {
+        name: "help",
+        aliases: &[],
+        args: "",
+        description: "Show the help popup",
+        run: run_help,
+        complete: complete_nothing,
+    },
+    Command {
+        name: "quit",
+        aliases: &["q"],
+        args: "",
+        description: "Quit lazy-top",
+        run: run_quit,
+        complete: complete_nothing,
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Command> {
+    COMMANDS
+        .iter()
+        .find(|command| command.name == name || command.aliases.contains(&name))
+}
+
+pub fn execute(app: &mut App, line: &str) -> Result<()> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(());
+    }
+    let (name, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let command = find(name).ok_or_else(|| {
+        anyhow!(
+            "unknown command `{}`, expected one of: {}",
+            name,
+            COMMANDS.map(|command| command.name).join(", ")
+        )
+    })?;
+    (command.run)(app, args.trim()).map_err(|err| anyhow!("{}: {}", command.name, err))
+}
+
+// Completes the word under the cursor (the last one). Pressing Tab again on a complete candidate
+// moves on to the next one, so a list can be cycled through.
+pub fn complete(app: &App, line: &str) -> Option<String> {
+    let mut words: Vec<&str> = line.split_whitespace().collect();
+    if line.is_empty() || line.ends_with(char::is_whitespace) {
+        words.push("");
+    }
+    let current = words.pop()?;
+
+    let candidates: Vec<String> = match words.first() {
+        None => COMMANDS
+            .iter()
+            .map(|command| command.name.to_string())
+            .collect(),
+        Some(name) => match find(name) {
+            Some(command) => (command.complete)(app, words.len() - 1, current),
+            None => Vec::new(),
+        },
+    };
+
+    let completed = match candidates
+        .iter()
+        .position(|candidate| candidate.eq_ignore_ascii_case(current))
+    {
+        Some(index) => &candidates[(index + 1) % candidates.len()],
+        None => {
+            let prefix = current.to_lowercase();
+            candidates
+                .iter()
+                .find(|candidate| candidate.to_lowercase().starts_with(&prefix))?
+        }
+    };
+    words.push(completed);
+    Some(words.join(" "))
+}
+
+// Live preview while completing `theme`; Esc restores the original.
+pub fn preview(app: &mut App) {
+    let mut words = app.command_buffer.split_whitespace();
+    if let (Some("theme"), Some(name)) = (words.next(), words.next()) {
+        if let Some(theme) = app.themes.find(name) {
+            app.theme = theme.clone();
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------------------------------------------
+
+fn split_args<'a>(name: &str, args: &'a str, count: RangeInclusive<usize>) -> Result<Vec<&'a str>> {
+    let words: Vec<&str> = args.split_whitespace().collect();
+    if !count.contains(&words.len()) {
+        return Err(usage(name));
+    }
+    Ok(words)
+}
+
+fn usage(name: &str) -> anyhow::Error {
+    let args = find(name).map(|command| command.args).unwrap_or_default();
+    anyhow!("usage: {} {}", name, args)
+}
+
+fn parse_pid(s: &str) -> Result<u32> {
+    s.parse().map_err(|_| anyhow!("invalid PID `{}`", s))
+}
+
+fn process_name(app: &App, pid: u32) -> Result<String> {
+    app.process_name(pid)
+        .ok_or_else(|| anyhow!("no process with PID {}", pid))
+}
+
+// `sort cpu`, `sort name asc` or `sort cpu desc then memory`. Without `then` the secondary
+// column is cleared.
+fn run_sort(app: &mut App, args: &str) -> Result<()> {
+    let words = split_args("sort", args, 1..=5)?;
+    let (primary, secondary) = match words.iter().position(|word| *word == "then") {
+        Some(then) => (&words[..then], Some(&words[then + 1..])),
+        None => (&words[..], None),
+    };
+    let (sort_by, sort_order) = parse_sort_key(primary)?;
+    let secondary = match secondary {
+        Some(words) => {
+            let (column, order) = parse_sort_key(words)?;
+            if column == sort_by {
+                bail!("`{}` is already the main sort column", column.id());
+            }
+            Some((column, order.unwrap_or_else(|| column.default_order())))
+        }
+        None => None,
+    };
+    app.secondary_sort = secondary;
+    app.set_sort(sort_by, sort_order);
+    Ok(())
+}
+
+fn parse_sort_key(words: &[&str]) -> Result<(Column, Option<SortOrder>)> {
+    let (id, order) = match words {
+        [id] => (id, None),
+        [id, order] => (id, Some(order)),
+        _ => bail!("usage: sort <col> [asc|desc] [then <col> [asc|desc]]"),
+    };
+    let column = parse_column(id)?;
+    let order = match order {
+        Some(order) => Some(
+            SortOrder::from_name(order)
+                .ok_or_else(|| anyhow!("expected `asc` or `desc`, got `{}`", order))?,
+        ),
+        None => None,
+    };
+    Ok((column, order))
+}
+
+fn parse_column(id: &str) -> Result<Column> {
+    Column::from_id(id).ok_or_else(|| {
+        anyhow!(
+            "unknown column `{}`, expected one of: {}",
+            id,
+            Column::ALL.map(|column| column.id()).join(", ")
+        )
+    })
+}
+
+fn run_filter(app: &mut App, args: &str) -> Result<()> {
+    if !args.is_empty() {
+        Filter::parse(args)?;
+    }
+    app.set_filter_query(args.to_string());
+    Ok(())
+}
+
+fn run_kill(app: &mut App, args: &str) -> Result<()> {
+    let words = split_args("kill", args, 1..=2)?;
+    let pid = parse_pid(words[0])?;
+    let choice = match words.get(1) {
+        Some(signal) => {
+            parse_signal(signal).ok_or_else(|| anyhow!("unknown signal `{}`", signal))?
+        }
+        None => SignalChoice::Named(SIGNALS[0].signal),
+    };
+    let name = process_name(app, pid)?;
+    app.signal_process(pid, &name, choice);
+    Ok(())
+}
+
+// The processes a command is about: the PID given as the first word, or else the marked
+// processes or the selected one.
+fn targets<'a>(
+    app: &App,
+    words: &'a [&'a str],
+    explicit: bool,
+) -> Result<(Vec<Target>, &'a [&'a str])> {
+    if explicit {
+        let pid = parse_pid(words[0])?;
+        return Ok((pid_targets(app, Some(pid))?, &words[1..]));
+    }
+    Ok((pid_targets(app, None)?, words))
+}
+
+fn pid_targets(app: &App, pid: Option<u32>) -> Result<Vec<Target>> {
+    if let Some(pid) = pid {
+        let name = process_name(app, pid)?;
+        return Ok(vec![Target { pid, name }]);
+    }
+    let targets = app.targets();
+    if targets.is_empty() {
+        bail!("no process selected");
+    }
+    Ok(targets)
+}
+
+fn run_renice(app: &mut App, args: &str) -> Result<()> {
+    let words = split_args("renice", args, 1..=2)?;
+    let (targets, words) = targets(app, &words, words.len() == 2)?;
+    let nice: i32 = words[0]
+        .parse()
+        .ok()
+        .filter(|nice| NICE_RANGE.contains(nice))
+        .ok_or_else(|| {
+            anyhow!(
+                "nice value must be a number from {} to {}",
+                NICE_RANGE.start(),
+                NICE_RANGE.end()
+            )
+        })?;
+    app.renice_processes(&targets, nice);
+    Ok(())
+}
+
+// `ionice idle`, `ionice be 7` or `ionice 1234 rt 0`; the level defaults to 4.
+fn run_ionice(app: &mut App, args: &str) -> Result<()> {
+    let (pid, priority) = parse_ionice(args)?;
+    let targets = pid_targets(app, pid)?;
+    app.set_io_priorities(&targets, priority);
+    Ok(())
+}
+
+// A leading number is a PID only when a class follows it, so `ionice 1234` is a usage error
+// rather than an unknown class.
+fn parse_ionice(args: &str) -> Result<(Option<u32>, IoPriority)> {
+    let words = split_args("ionice", args, 1..=3)?;
+    let (pid, words) = match words[0].parse() {
+        Ok(pid) => (Some(pid), &words[1..]),
+        Err(_) => (None, &words[..]),
+    };
+    if !(1..=2).contains(&words.len()) {
+        return Err(usage("ionice"));
+    }
+    let class = IoClass::from_name(words[0]).ok_or_else(|| {
+        anyhow!(
+            "unknown I/O class `{}`, expected one of: {}",
+            words[0],
+            IoClass::ALL.map(|class| class.name()).join(", ")
+        )
+    })?;
+    let level = match words.get(1) {
+        Some(_) if !class.has_level() => bail!("the {} class has no levels", class.name()),
+        Some(level) => level
+            .parse()
+            .ok()
+            .filter(|level| IO_LEVELS.contains(level))
+            .ok_or_else(|| {
+                anyhow!(
+                    "I/O level must be a number from {} to {}",
+                    IO_LEVELS.start(),
+                    IO_LEVELS.end()
+                )
+            })?,
+        None => DEFAULT_IO_LEVEL,
+    };
+    Ok((pid, IoPriority { class, level }))
+}
+
+// `throttle 20`, `throttle 1234 50%` or `throttle off`. The percentage is of one core, like the
+// CPU column, so a multi-threaded process may be given more than 100.
+fn run_throttle(app: &mut App, args: &str) -> Result<()> {
+    let words = split_args("throttle", args, 1..=2)?;
+    let (targets, words) = targets(app, &words, words.len() == 2)?;
+    if words[0].eq_ignore_ascii_case("off") {
+        app.throttle_processes(targets, None);
+        return Ok(());
+    }
+    let max = 100.0 * app.sys.cpus().len().max(1) as f32;
+    let limit: f32 = words[0]
+        .trim_end_matches('%')
+        .parse()
+        .ok()
+        .filter(|limit| *limit > 0.0 && *limit <= max)
+        .ok_or_else(|| {
+            anyhow!(
+                "expected a CPU percentage above 0 and up to {}, or `off`",
+                max
+            )
+        })?;
+    app.throttle_processes(targets, Some(limit));
+    Ok(())
+}
+
+fn run_interval(app: &mut App, args: &str) -> Result<()> {
+    let words = split_args("interval", args, 1..=1)?;
+    let interval = parse_duration(words[0])
+        .ok_or_else(|| anyhow!("expected a duration such as \"250ms\" or \"1s\""))?;
+    if interval < MIN_INTERVAL {
+        bail!("must be at least {}ms", MIN_INTERVAL.as_millis());
+    }
+    if interval > MAX_INTERVAL {
+        bail!("must be at most {}", format_duration(MAX_INTERVAL));
+    }
+    app.refresh_interval = interval;
+    app.set_message(format!(
+        "Refresh interval set to {}",
+        format_duration(interval)
+    ));
+    Ok(())
+}
+
+fn run_goto(app: &mut App, args: &str) -> Result<()> {
+    let words = split_args("goto", args, 1..=1)?;
+    let pid = parse_pid(words[0])?;
+    if app.select_pid(pid) {
+        Ok(())
+    } else if app.process_name(pid).is_some() {
+        bail!("PID {} is hidden by the current filter", pid)
+    } else {
+        bail!("no process with PID {}", pid)
+    }
+}
+
+fn run_columns(app: &mut App, args: &str) -> Result<()> {
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let mut columns = app.columns.clone();
+    match words.as_slice() {
+        [] => {
+            app.open_column_picker();
+            return Ok(());
+        }
+        ["move", id, position] => {
+            let column = parse_column(id)?;
+            let position: usize = position
+                .parse()
+                .ok()
+                .filter(|&position| position >= 1)
+                .ok_or_else(|| anyhow!("invalid position `{}`, expected 1 or more", position))?;
+            let Some(from) = columns.iter().position(|&shown| shown == column) else {
+                bail!("column `{}` is not shown", column.id());
+            };
+            columns.remove(from);
+            columns.insert((position - 1).min(columns.len()), column);
+        }
+        ["move", ..] => bail!("usage: columns move <column> <position>"),
+        [change] if change.starts_with('+') => {
+            let column = parse_column(&change[1..])?;
+            if !columns.contains(&column) {
+                columns.push(column);
+            }
+        }
+        [change] if change.starts_with('-') => {
+            let column = parse_column(&change[1..])?;
+            columns.retain(|&shown| shown != column);
+        }
+        ids => {
+            columns.clear();
+            for id in ids {
+                let column = parse_column(id)?;
+                if !columns.contains(&column) {
+                    columns.push(column);
+                }
+            }
+        }
+    }
+    if columns.is_empty() {
+        bail!("at least one column must stay visible");
+    }
+
+    app.set_columns(columns);
+    Ok(())
+}
+
+fn run_export(app: &mut App, args: &str) -> Result<()> {
+    if args.is_empty() {
+        bail!("usage: export <path>");
+    }
+    let path = expand_home(args);
+    let count = export(app, &path)?;
+    // Unlike signals, the export is of the list as shown, so marked processes that are filtered
+    // out or collapsed are left out; the message says so.
+    let marked = app.marked.len();
+    let message = match marked {
+        0 => format!("Exported {} processes to {}", count, path.display()),
+        _ if count == marked => {
+            format!("Exported {} marked processes to {}", count, path.display())
+        }
+        _ => format!(
+            "Exported {} of {} marked processes to {} ({} not listed)",
+            count,
+            marked,
+            path.display(),
+            marked - count
+        ),
+    };
+    app.set_message(message);
+    Ok(())
+}
+
+fn run_theme(app: &mut App, args: &str) -> Result<()> {
+    let words = split_args("theme", args, 1..=1)?;
+    let theme = app.themes.find(words[0]).cloned().ok_or_else(|| {
+        anyhow!(
+            "unknown theme `{}`, expected one of: {}",
+            words[0],
+            app.themes.names().join(", ")
+        )
+    })?;
+    app.theme = theme;
+    Ok(())
+}
+
+fn run_tree(app: &mut App, args: &str) -> Result<()> {
+    let words = split_args("tree", args, 0..=1)?;
+    match words.first() {
+        Some(&"on") => app.tree_view = true,
+        Some(&"off") => app.tree_view = false,
+        Some(other) => bail!("expected `on` or `off`, got `{}`", other),
+        None => app.toggle_tree_view(),
+    }
+    Ok(())
+}
+
+fn run_help(app: &mut App, args: &str) -> Result<()> {
+    split_args("help", args, 0..=0)?;
+    app.open_help();
+    Ok(())
+}
+
+fn run_quit(app: &mut App, args: &str) -> Result<()> {
+    split_args("quit", args, 0..=0)?;
+    app.should_quit = true;
+    Ok(())
+}
+
+//-----------------------------------------------------------------------------------------------------------------
+
+fn complete_nothing(_app: &App, _position: usize, _current: &str) -> Vec<String> {
+    Vec::new()
+}
+
+fn complete_sort(_app: &App, position: usize, _current: &str) -> Vec<String> {
+    let ids = Column::ALL.map(|column| column.id().to_string()).to_vec();
+    let words = |words: &[&str]| {
+        words
+            .iter()
+            .map(|word| word.to_string())
+            .collect::<Vec<_>>()
+    };
+    match position {
+        0 => ids,
+        1 => words(&["asc", "desc", "then"]),
+        2 => [words(&["then"]), ids].concat(),
+        3 => [ids, words(&["asc", "desc"])].concat(),
+        4 => words(&["asc", "desc"]),
+        _ => Vec::new(),
+    }
+}
+
+// Listed PIDs, so Tab walks through what is on screen.
+fn complete_pid(app: &App, position: usize, _current: &str) -> Vec<String> {
+    if position != 0 {
+        return Vec::new();
+    }
+    app.processes
+        .iter()
+        .map(|item| item.pid.to_string())
+        .collect()
+}
+
+fn complete_kill(app: &App, position: usize, current: &str) -> Vec<String> {
+    match position {
+        0 => complete_pid(app, position, current),
+        1 => SIGNALS.iter().map(|info| info.name.to_string()).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn complete_ionice(app: &App, position: usize, current: &str) -> Vec<String> {
+    let classes = IoClass::ALL.map(|class| class.name().to_string());
+    match position {
+        0 => classes
+            .into_iter()
+            .chain(complete_pid(app, position, current))
+            .collect(),
+        1 => classes.to_vec(),
+        _ => Vec::new(),
+    }
+}
+
+fn complete_throttle(app: &App, position: usize, current: &str) -> Vec<String> {
+    match position {
+        0 => std::iter::once("off".to_string())
+            .chain(complete_pid(app, position, current))
+            .collect(),
+        1 => vec!["off".to_string()],
+        _ => Vec::new(),
+    }
+}
+
+fn complete_columns(_app: &App, position: usize, current: &str) -> Vec<String> {
+    if position == 0 && "move".starts_with(current) && !current.is_empty() {
+        return vec!["move".to_string()];
+    }
+    let sign = current
+        .chars()
+        .next()
+        .filter(|c| *c == '+' || *c == '-')
+        .map(String::from)
+        .unwrap_or_default();
+    Column::ALL
+        .iter()
+        .map(|column| format!("{}{}", sign, column.id()))
+        .collect()
+}
+
+fn complete_theme(app: &App, position: usize, _current: &str) -> Vec<String> {
+    if position != 0 {
+        return Vec::new();
+    }
+    app.themes
+        .names()
+        .iter()
+        .map(|name| name.to_string())
+        .collect()
+}
+
+fn complete_tree(_app: &App, position: usize, _current: &str) -> Vec<String> {
+    if position != 0 {
+        return Vec::new();
+    }
+    vec!["on".to_string(), "off".to_string()]
+}
+
+// Entries of the directory being typed; directories keep a trailing slash so Tab can descend.
+fn complete_path(_app: &App, position: usize, current: &str) -> Vec<String> {
+    if position != 0 {
+        return Vec::new();
+    }
+    let (dir, _) = current.rsplit_once('/').unwrap_or(("", current));
+    let prefix = if current.contains('/') {
+        format!("{}/", dir)
+    } else {
+        String::new()
+    };
+    let read_from = if current.contains('/') {
+        expand_home(&prefix)
+    } else {
+        PathBuf::from(".")
+    };
+
+    let Ok(entries) = fs::read_dir(read_from) else {
+        return Vec::new();
+    };
+    let mut candidates: Vec<String> = entries
+        .flatten()
+        .map(|entry| {
+            let mut name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+            if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                name.push('/');
+            }
+            name
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+//-----------------------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn priority(class: IoClass, level: u8) -> IoPriority {
+        IoPriority { class, level }
+    }
+
+    #[test]
+    fn ionice_takes_a_pid_only_before_a_class() {
+        assert_eq!(
+            parse_ionice("1234 rt 0").unwrap(),
+            (Some(1234), priority(IoClass::Realtime, 0))
+        );
+        assert_eq!(
+            parse_ionice("be 7").unwrap(),
+            (None, priority(IoClass::BestEffort, 7))
+        );
+        assert_eq!(
+            parse_ionice("idle").unwrap(),
+            (None, priority(IoClass::Idle, DEFAULT_IO_LEVEL))
+        );
+    }
+
+    #[test]
+    fn ionice_without_a_class_is_a_usage_error() {
+        for args in ["1234", "", "be 7 1", "1234 be 7 1"] {
+            let err = parse_ionice(args).unwrap_err().to_string();
+            assert!(err.starts_with("usage: ionice "), "{:?}: {}", args, err);
+        }
+    }
+
+    #[test]
+    fn ionice_rejects_unknown_classes_and_levels() {
+        let err = parse_ionice("1234 fast").unwrap_err().to_string();
+        assert!(err.starts_with("unknown I/O class `fast`"), "{}", err);
+        let err = parse_ionice("be 9").unwrap_err().to_string();
+        assert!(err.starts_with("I/O level must be"), "{}", err);
+        let err = parse_ionice("idle 3").unwrap_err().to_string();
+        assert_eq!(err, "the idle class has no levels");
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Deserializer};
+use toml_edit::{Array, DocumentMut};
+
+use crate::app::SortOrder;
+use crate::columns::Column;
+use crate::keymap::{KeyList, Keymap};
+use crate::signals::TreeSignalOrder;
+use crate::theme::DEFAULT_THEME;
+
+//-----------------------------------------------------------------------------------------------------------------
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub theme: String,
+    pub sort: Column,
+    pub sort_order: Option<SortOrder>,
+    pub secondary_sort: Option<Column>,
+    pub secondary_sort_order: Option<SortOrder>,
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+    pub history_length: usize,
+    pub mouse: bool,
+    pub columns: Vec<Column>,
+    pub tree_signal_order: TreeSignalOrder,
+    pub panels: Panels,
+    pub keys: BTreeMap<String, KeyList>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Panels {
+    pub system: bool,
+    pub cpu_graph: bool,
+    pub memory_graph: bool,
+    pub swap_graph: bool,
+    pub cpu_cores: bool,
+    pub core_heatmap: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            theme: DEFAULT_THEME.to_string(),
+            sort: Column::Cpu,
+            sort_order: None,
+            secondary_sort: None,
+            secondary_sort_order: None,
+            interval: Duration::from_millis(250),
+            history_length: 100,
+            mouse: true,
+            columns: Column::default_set(),
+            tree_signal_order: TreeSignalOrder::ChildrenFirst,
+            panels: Panels::default(),
+            keys: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for Panels {
+    fn default() -> Self {
+        Panels {
+            system: true,
+            cpu_graph: true,
+            memory_graph: true,
+            swap_graph: true,
+            cpu_cores: true,
+            core_heatmap: false,
+        }
+    }
+}
+
+impl Config {
+    // Reads `config.toml` from the config dir. A missing file is not an error and yields the defaults.
+    pub fn load() -> Result<Config> {
+        let Some(path) = config_path() else {
+            return Ok(Config::default());
+        };
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read {}", path.display()));
+            }
+        };
+
+        let config: Config = toml::from_str(&contents)
+            .with_context(|| format!("invalid config file {}", path.display()))?;
+        config
+            .validate()
+            .with_context(|| format!("invalid config file {}", path.display()))?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.interval < MIN_INTERVAL {
+            bail!("`interval` must be at least {}ms", MIN_INTERVAL.as_millis());
+        }
+        if self.interval > MAX_INTERVAL {
+            bail!(
+                "`interval` must be at most {}",
+                format_duration(MAX_INTERVAL)
+            );
+        }
+        if !(1..=MAX_HISTORY_LENGTH).contains(&self.history_length) {
+            bail!("`history_length` must be from 1 to {}", MAX_HISTORY_LENGTH);
+        }
+        if self.secondary_sort == Some(self.sort) {
+            bail!("`secondary_sort` must differ from `sort`");
+        }
+        if self.columns.is_empty() {
+            bail!("`columns` must list at least one column");
+        }
+        Keymap::from_config(&self.keys)?;
+        Ok(())
+    }
+}
+
+// Samples kept per chart; far more than any terminal is wide.
+pub const MAX_HISTORY_LENGTH: usize = 10_000;
+
+pub const MIN_INTERVAL: Duration = Duration::from_millis(50);
+// Anything slower looks frozen, and huge values would overflow the timing arithmetic.
+pub const MAX_INTERVAL: Duration = Duration::from_secs(3600);
+
+// Sets `columns` in config.toml, creating the file if needed. The rest of the file, comments
+// included, is left as it was.
+pub fn save_columns(columns: &[Column]) -> Result<PathBuf> {
+    let path = config_path().ok_or_else(|| anyhow!("neither $XDG_CONFIG_HOME nor $HOME is set"))?;
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            return Err(err).with_context(|| format!("failed to read {}", path.display()));
+        }
+    };
+
+    let mut document: DocumentMut = contents
+        .parse()
+        .with_context(|| format!("invalid config file {}", path.display()))?;
+    let ids: Array = columns.iter().map(|column| column.id()).collect();
+    document["columns"] = toml_edit::value(ids);
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    fs::write(&path, document.to_string())
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+//-----------------------------------------------------------------------------------------------------------------
+
+// $XDG_CONFIG_HOME/lazy-top, falling back to ~/.config/lazy-top.
+pub fn config_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+fn config_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config.toml"))
+}
+
+// $XDG_DATA_HOME/lazy-top, falling back to ~/.local/share/lazy-top.
+pub fn data_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(fallback)))
+        .map(|dir| dir.join("lazy-top"))
+}
+
+// Accepts "250ms", "2s", "1.5s" or a bare number of milliseconds.
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    if let Some(ms) = s.strip_suffix("ms") {
+        ms.trim().parse().ok().map(Duration::from_millis)
+    } else if let Some(secs) = s.strip_suffix('s') {
+        // Negative, NaN and out-of-range values are rejected rather than panicking.
+        secs.trim()
+            .parse::<f64>()
+            .ok()
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+    } else {
+        s.parse().ok().map(Duration::from_millis)
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    if millis >= 1000 && millis.is_multiple_of(1000) {
+        format!("{}s", millis / 1000)
+    } else {
+        format!("{}ms", millis)
+    }
+}
+
+fn deserialize_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawDuration {
+        Millis(u64),
+        Text(String),
+    }
+
+    match RawDuration::deserialize(deserializer)? {
+        RawDuration::Millis(ms) => Ok(Duration::from_millis(ms)),
+        RawDuration::Text(text) => parse_duration(&text).ok_or_else(|| {
+            serde::de::Error::custom(format!(
+                "invalid duration `{}`, expected e.g. \"250ms\" or \"1s\"",
+                text
+            ))
+        }),
+    }
+}
+
+//-----------------------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_config(text: &str) -> Result<Config> {
+        let config: Config = toml::from_str(text)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    #[test]
+    fn durations_take_ms_s_or_bare_milliseconds() {
+        assert_eq!(parse_duration("250ms"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_duration("2s"), Some(Duration::from_secs(2)));
+        assert_eq!(parse_duration("1.5s"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_duration(" 10 ms "), Some(Duration::from_millis(10)));
+        assert_eq!(parse_duration("300"), Some(Duration::from_millis(300)));
+    }
+
+    #[test]
+    fn durations_reject_unknown_units() {
+        for text in ["", "ms", "s", "2m", "1h", "5 sec", "abc", "1.5ms"] {
+            assert_eq!(parse_duration(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn durations_reject_negative_nan_and_huge_values() {
+        for text in [
+            "-1s", "-5ms", "-5", "NaNs", "nans", "infs", "1e20s", "1e400s",
+        ] {
+            assert_eq!(parse_duration(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn interval_must_be_in_range() {
+        assert!(parse_config("interval = \"50ms\"").is_ok());
+        assert!(parse_config("interval = \"3600s\"").is_ok());
+        assert!(parse_config("interval = \"49ms\"").is_err());
+        assert!(parse_config("interval = \"3601s\"").is_err());
+        assert!(parse_config("interval = 9223372036854775807").is_err());
+        assert!(parse_config("interval = \"1e20s\"").is_err());
+    }
+
+    #[test]
+    fn history_length_must_be_in_range() {
+        assert!(parse_config("history_length = 1").is_ok());
+        assert!(parse_config("history_length = 10000").is_ok());
+        assert!(parse_config("history_length = 0").is_err());
+        assert!(parse_config("history_length = 10001").is_err());
+        assert!(parse_config("history_length = 9223372036854775807").is_err());
+    }
+}
@@ -0,0 +1,226 @@
+use std::path::Path;
+
+use sysinfo::{Pid, Process};
+
+use crate::app::{App, state_code, state_name};
+use crate::columns::{format_bytes, format_cpu_time, format_elapsed, local_time};
+use crate::procfs;
+
+// Parent chains longer than this are cut short rather than trusted to end.
+const MAX_PARENTS: usize = 64;
+
+//-----------------------------------------------------------------------------------------------------------------
+
+pub struct Section {
+    pub title: &'static str,
+    pub rows: Vec<(String, String)>,
+}
+
+// Everything known about one process, read fresh from the last sample so the view stays live.
+// None once the process has exited.
+pub fn collect(app: &App, pid: u32) -> Option<Vec<Section>> {
+    let process = app.sys.process(Pid::from_u32(pid))?;
+    let row = |label: &str, value: String| (label.to_string(), value);
+    let path = |path: Option<&Path>| {
+        path.map(|path| path.display().to_string())
+            .unwrap_or_else(|| "-".to_string())
+    };
+    let extra = procfs::read(pid);
+
+    let process_rows = vec![
+        row("Name", process.name().to_string_lossy().into_owned()),
+        row("PID", pid.to_string()),
+        row("Parents", parent_chain(app, process)),
+        row(
+            "State",
+            format!(
+                "{} ({})",
+                state_code(process.status()),
+                state_name(process.status())
+            ),
+        ),
+        row("User", user(app, process)),
+        row("Group", group(app, process)),
+        row(
+            "Threads",
+            process
+                .tasks()
+                .map(|tasks| (tasks.len() + 1).to_string())
+                .unwrap_or_else(|| "-".to_string()),
+        ),
+        row(
+            "Nice",
+            extra
+                .nice
+                .map(|nice| nice.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+        ),
+        row(
+            "I/O priority",
+            extra
+                .io_priority
+                .map(|priority| priority.label())
+                .unwrap_or_else(|| "-".to_string()),
+        ),
+        row("Started", format_timestamp(process.start_time())),
+        row("Running for", format_elapsed(process.run_time())),
+    ];
+
+    let command_rows = vec![
+        row("Command line", command_line(process)),
+        row("Executable", path(process.exe())),
+        row("Working dir", path(process.cwd())),
+    ];
+
+    let cpu_rows = vec![
+        row("Usage", format!("{:.2}%", process.cpu_usage())),
+        row("CPU time", format_cpu_time(process.accumulated_cpu_time())),
+    ];
+
+    let breakdown = procfs::memory_breakdown(pid);
+    let optional = |bytes: Option<u64>| bytes.map(format_bytes).unwrap_or_else(|| "-".to_string());
+    let memory_rows = vec![
+        row("Resident", format_bytes(process.memory())),
+        row("  anonymous", optional(breakdown.anonymous)),
+        row("  file-backed", optional(breakdown.file)),
+        row(
+            "  shared",
+            optional(breakdown.shared.or(extra.shared_memory)),
+        ),
+        row("Virtual", format_bytes(process.virtual_memory())),
+        row("Swapped", optional(breakdown.swap)),
+    ];
+
+    let disk = process.disk_usage();
+    let seconds = app.sample_period.as_secs_f64().max(0.001);
+    let rate = |bytes: u64| format!("{}/s", format_bytes((bytes as f64 / seconds) as u64));
+    let disk_rows = vec![
+        row("Read", format_bytes(disk.total_read_bytes)),
+        row("Written", format_bytes(disk.total_written_bytes)),
+        row("Reading", rate(disk.read_bytes)),
+        row("Writing", rate(disk.written_bytes)),
+    ];
+
+    let mut environment: Vec<(String, String)> = process
+        .environ()
+        .iter()
+        .map(|entry| {
+            let entry = entry.to_string_lossy();
+            match entry.split_once('=') {
+                Some((key, value)) => (key.to_string(), value.to_string()),
+                None => (entry.into_owned(), String::new()),
+            }
+        })
+        .collect();
+    environment.sort();
+    if environment.is_empty() {
+        environment.push(row("-", "(empty or not readable)".to_string()));
+    }
+
+    Some(vec![
+        Section {
+            title: "Process",
+            rows: process_rows,
+        },
+        Section {
+            title: "Command",
+            rows: command_rows,
+        },
+        Section {
+            title: "CPU",
+            rows: cpu_rows,
+        },
+        Section {
+            title: "Memory",
+            rows: memory_rows,
+        },
+        Section {
+            title: "Disk I/O",
+            rows: disk_rows,
+        },
+        Section {
+            title: "Environment",
+            rows: environment,
+        },
+    ])
+}
+
+fn command_line(process: &Process) -> String {
+    let args: Vec<_> = process
+        .cmd()
+        .iter()
+        .map(|arg| arg.to_string_lossy())
+        .collect();
+    if args.is_empty() {
+        "-".to_string()
+    } else {
+        args.join(" ")
+    }
+}
+
+// "bash (816) ← sshd (700) ← systemd (1)", nearest parent first.
+fn parent_chain(app: &App, process: &Process) -> String {
+    let mut chain = Vec::new();
+    let mut parent = process.parent();
+    while let Some(pid) = parent {
+        if chain.len() == MAX_PARENTS {
+            chain.push("…".to_string());
+            break;
+        }
+        match app.sys.process(pid) {
+            Some(process) => {
+                chain.push(format!("{} ({})", process.name().to_string_lossy(), pid));
+                parent = process.parent();
+            }
+            None => {
+                chain.push(pid.to_string());
+                break;
+            }
+        }
+    }
+    if chain.is_empty() {
+        "-".to_string()
+    } else {
+        chain.join(" ← ")
+    }
+}
+
+// "alice (1000)", with the effective user added when it differs.
+fn user(app: &App, process: &Process) -> String {
+    let name = |uid: &sysinfo::Uid| match app.users.get_user_by_id(uid) {
+        Some(user) => format!("{} ({})", user.name(), **uid),
+        None => (**uid).to_string(),
+    };
+    match (process.user_id(), process.effective_user_id()) {
+        (Some(real), Some(effective)) if real != effective => {
+            format!("{}, effective {}", name(real), name(effective))
+        }
+        (Some(real), _) => name(real),
+        (None, _) => "-".to_string(),
+    }
+}
+
+fn group(app: &App, process: &Process) -> String {
+    let Some(gid) = process.group_id() else {
+        return "-".to_string();
+    };
+    match app.groups.list().iter().find(|group| *group.id() == gid) {
+        Some(group) => format!("{} ({})", group.name(), *gid),
+        None => (*gid).to_string(),
+    }
+}
+
+fn format_timestamp(secs: u64) -> String {
+    match local_time(secs) {
+        Some(time) => format!(
+            "{}-{:02}-{:02} {:02}:{:02}:{:02}",
+            time.year,
+            time.month + 1,
+            time.day,
+            time.hour,
+            time.minute,
+            time.second
+        ),
+        None => "-".to_string(),
+    }
+}
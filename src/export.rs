@@ -0,0 +1,101 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde_json::{Map, Value, json};
+
+use crate::app::{App, ProcessItem, state_code};
+use crate::columns::Column;
+
+//-----------------------------------------------------------------------------------------------------------------
+
+// Writes the visible processes, only the marked ones if any are, with the visible columns. A
+// `.json` path gets an array of objects, anything else CSV. Values are raw (bytes, percent, epoch
+// seconds, seconds running, milliseconds of CPU time) rather than formatted for the screen;
+// missing values are null or empty.
+pub fn export(app: &App, path: &Path) -> Result<usize> {
+    let is_json = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+
+    let processes: Vec<&ProcessItem> = app
+        .processes
+        .iter()
+        .filter(|item| app.marked.is_empty() || app.marked.contains(&item.pid))
+        .collect();
+    let contents = if is_json {
+        to_json(&app.columns, &processes)?
+    } else {
+        to_csv(&app.columns, &processes)
+    };
+    fs::write(path, contents).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(processes.len())
+}
+
+fn value(column: Column, item: &ProcessItem) -> Value {
+    match column {
+        Column::Pid => json!(item.pid),
+        Column::Ppid => json!(item.parent),
+        Column::User => json!(item.user),
+        Column::Name => json!(item.name),
+        Column::State => json!(state_code(item.status).to_string()),
+        Column::Threads => json!(item.threads),
+        Column::Nice => json!(item.nice),
+        Column::IoPriority => json!(item.io_priority.map(|priority| priority.label())),
+        Column::Cpu => json!((item.cpu_usage as f64 * 100.0).round() / 100.0),
+        Column::Memory => json!(item.memory),
+        Column::Virtual => json!(item.virtual_memory),
+        Column::Shared => json!(item.shared_memory),
+        Column::Start => json!(item.start_time),
+        Column::Elapsed => json!(item.run_time),
+        Column::CpuTime => json!(item.cpu_time),
+        Column::Command => json!(item.cmd),
+        Column::DiskRead => json!(item.disk_read),
+        Column::DiskWrite => json!(item.disk_write),
+    }
+}
+
+fn to_json(columns: &[Column], processes: &[&ProcessItem]) -> Result<String> {
+    let rows: Vec<Value> = processes
+        .iter()
+        .map(|item| {
+            let row: Map<String, Value> = columns
+                .iter()
+                .map(|&column| (column.id().to_string(), value(column, item)))
+                .collect();
+            Value::Object(row)
+        })
+        .collect();
+    Ok(serde_json::to_string_pretty(&rows)? + "\n")
+}
+
+fn to_csv(columns: &[Column], processes: &[&ProcessItem]) -> String {
+    let mut out = columns
+        .iter()
+        .map(|column| column.id())
+        .collect::<Vec<_>>()
+        .join(",");
+    out.push('\n');
+
+    for item in processes {
+        let fields: Vec<String> = columns
+            .iter()
+            .map(|&column| match value(column, item) {
+                Value::String(text) => csv_field(&text),
+                Value::Null => String::new(),
+                other => other.to_string(),
+            })
+            .collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,681 @@
+use anyhow::{Result, anyhow, bail};
+use regex::Regex;
+
+use crate::app::{ProcessItem, state_code, state_name};
+
+//-----------------------------------------------------------------------------------------------------------------
+
+// A parsed filter expression. Bare words match the process name (case-insensitive substring),
+// `field op value` compares a field, and terms combine with `and`, `or`, `not` and parentheses.
+//
+//     firefox                  cpu>5 and mem>500MB          user=postgres or user=root
+//     state=Z                  cmd~"--worker"               not name~"^kworker" -sleep
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Name(String),
+    Number(NumberField, Op, f64),
+    Text(TextField, bool, String),
+    Regex(TextField, bool, Regex),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumberField {
+    Pid,
+    Ppid,
+    Cpu,
+    Memory,
+    Threads,
+    // Both in seconds.
+    CpuTime,
+    Elapsed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextField {
+    Name,
+    Cmd,
+    User,
+    State,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Match,
+    NotMatch,
+}
+
+enum Field {
+    Number(NumberField),
+    Text(TextField),
+}
+
+pub const FIELDS: [&str; 11] = [
+    "pid", "ppid", "name", "cmd", "user", "state", "cpu", "mem", "threads", "time", "elapsed",
+];
+
+impl Field {
+    fn from_name(name: &str) -> Option<Field> {
+        Some(match name.to_lowercase().as_str() {
+            "pid" => Field::Number(NumberField::Pid),
+            "ppid" => Field::Number(NumberField::Ppid),
+            "cpu" => Field::Number(NumberField::Cpu),
+            "mem" | "memory" => Field::Number(NumberField::Memory),
+            "threads" => Field::Number(NumberField::Threads),
+            "time" => Field::Number(NumberField::CpuTime),
+            "elapsed" | "etime" => Field::Number(NumberField::Elapsed),
+            "name" => Field::Text(TextField::Name),
+            "cmd" | "command" => Field::Text(TextField::Cmd),
+            "user" => Field::Text(TextField::User),
+            "state" | "status" => Field::Text(TextField::State),
+            _ => return None,
+        })
+    }
+}
+
+impl Op {
+    fn symbol(self) -> &'static str {
+        match self {
+            Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Match => "~",
+            Op::NotMatch => "!~",
+        }
+    }
+
+    fn compare(self, left: f64, right: f64) -> bool {
+        match self {
+            Op::Eq | Op::Match => left == right,
+            Op::Ne | Op::NotMatch => left != right,
+            Op::Lt => left < right,
+            Op::Le => left <= right,
+            Op::Gt => left > right,
+            Op::Ge => left >= right,
+        }
+    }
+}
+
+impl Filter {
+    pub fn parse(input: &str) -> Result<Filter> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let filter = parser.or()?;
+        match parser.peek() {
+            None => Ok(filter),
+            Some(token) => bail!("unexpected {}", token.describe()),
+        }
+    }
+
+    pub fn matches(&self, item: &ProcessItem) -> bool {
+        match self {
+            Filter::And(left, right) => left.matches(item) && right.matches(item),
+            Filter::Or(left, right) => left.matches(item) || right.matches(item),
+            Filter::Not(inner) => !inner.matches(item),
+            Filter::Name(query) => item.name.to_lowercase().contains(query),
+            Filter::Number(field, op, value) => op.compare(number(item, *field), *value),
+            Filter::Text(TextField::State, negate, value) => {
+                // One letter compares the ps-style code exactly (T and t differ), longer values
+                // compare the state name.
+                let matched = if value.chars().count() == 1 {
+                    value.starts_with(state_code(item.status))
+                } else {
+                    state_name(item.status).eq_ignore_ascii_case(value)
+                };
+                matched != *negate
+            }
+            Filter::Text(field, negate, value) => {
+                text(item, *field).eq_ignore_ascii_case(value) != *negate
+            }
+            Filter::Regex(field, negate, regex) => {
+                let haystack = match field {
+                    TextField::State => state_code(item.status).to_string(),
+                    field => text(item, *field),
+                };
+                regex.is_match(&haystack) != *negate
+            }
+        }
+    }
+}
+
+fn number(item: &ProcessItem, field: NumberField) -> f64 {
+    match field {
+        NumberField::Pid => item.pid as f64,
+        NumberField::Ppid => item.parent.unwrap_or(0) as f64,
+        NumberField::Cpu => item.cpu_usage as f64,
+        NumberField::Memory => item.memory as f64,
+        NumberField::Threads => item.threads.unwrap_or(0) as f64,
+        NumberField::CpuTime => item.cpu_time as f64 / 1000.0,
+        NumberField::Elapsed => item.run_time as f64,
+    }
+}
+
+fn text(item: &ProcessItem, field: TextField) -> String {
+    match field {
+        TextField::Name => item.name.clone(),
+        TextField::Cmd => item.cmd.clone(),
+        TextField::User => item.user.clone(),
+        TextField::State => state_name(item.status).to_string(),
+    }
+}
+
+//-----------------------------------------------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Not,
+    And,
+    Or,
+    Op(Op),
+    Word(String),
+    Quoted(String),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::LParen => "`(`".to_string(),
+            Token::RParen => "`)`".to_string(),
+            Token::Not => "`not`".to_string(),
+            Token::And => "`and`".to_string(),
+            Token::Or => "`or`".to_string(),
+            Token::Op(op) => format!("`{}`", op.symbol()),
+            Token::Word(word) => format!("`{}`", word),
+            Token::Quoted(text) => format!("\"{}\"", text),
+        }
+    }
+
+    fn starts_term(&self) -> bool {
+        matches!(
+            self,
+            Token::LParen | Token::Not | Token::Word(_) | Token::Quoted(_)
+        )
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        let after_op = matches!(tokens.last(), Some(Token::Op(_)));
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' => {
+                chars.next();
+                tokens.push(if c == '(' {
+                    Token::LParen
+                } else {
+                    Token::RParen
+                });
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => match chars.next() {
+                            Some(escaped) if escaped == c || escaped == '\\' => text.push(escaped),
+                            Some(other) => {
+                                // Kept as typed, so regex escapes such as \d survive.
+                                text.push('\\');
+                                text.push(other);
+                            }
+                            None => bail!("unterminated quote"),
+                        },
+                        Some(quote) if quote == c => break,
+                        Some(other) => text.push(other),
+                        None => bail!("unterminated quote"),
+                    }
+                }
+                tokens.push(Token::Quoted(text));
+            }
+            '!' => {
+                chars.next();
+                tokens.push(match chars.peek() {
+                    Some('=') => {
+                        chars.next();
+                        Token::Op(Op::Ne)
+                    }
+                    Some('~') => {
+                        chars.next();
+                        Token::Op(Op::NotMatch)
+                    }
+                    _ => Token::Not,
+                });
+            }
+            '=' => {
+                chars.next();
+                if chars.peek() == Some(&'=') {
+                    chars.next();
+                }
+                tokens.push(Token::Op(Op::Eq));
+            }
+            '~' => {
+                chars.next();
+                tokens.push(Token::Op(Op::Match));
+            }
+            '<' | '>' => {
+                chars.next();
+                let or_equal = chars.peek() == Some(&'=');
+                if or_equal {
+                    chars.next();
+                }
+                tokens.push(Token::Op(match (c, or_equal) {
+                    ('<', false) => Op::Lt,
+                    ('<', true) => Op::Le,
+                    ('>', false) => Op::Gt,
+                    _ => Op::Ge,
+                }));
+            }
+            // A leading dash excludes, except where it is the sign of a value (`cpu>-1`).
+            '-' if !after_op => {
+                chars.next();
+                tokens.push(Token::Not);
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "()\"'!=~<>".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match word.to_lowercase().as_str() {
+                    "and" | "&&" if !after_op => Token::And,
+                    "or" | "||" if !after_op => Token::Or,
+                    "not" if !after_op => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Filter> {
+        let mut left = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.advance();
+            let right = self.and()?;
+            left = Filter::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    // Terms next to each other are joined with `and` as well.
+    fn and(&mut self) -> Result<Filter> {
+        let mut left = self.unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.advance();
+                }
+                Some(token) if token.starts_term() => {}
+                _ => return Ok(left),
+            }
+            let right = self.unary()?;
+            left = Filter::And(Box::new(left), Box::new(right));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Filter> {
+        if self.peek() == Some(&Token::Not) {
+            self.advance();
+            return Ok(Filter::Not(Box::new(self.unary()?)));
+        }
+        self.term()
+    }
+
+    fn term(&mut self) -> Result<Filter> {
+        match self.advance() {
+            Some(Token::LParen) => {
+                let inner = self.or()?;
+                match self.advance() {
+                    Some(Token::RParen) => Ok(inner),
+                    _ => bail!("missing `)`"),
+                }
+            }
+            Some(Token::Word(word)) => match self.peek() {
+                Some(Token::Op(op)) => {
+                    let op = *op;
+                    self.advance();
+                    let field = Field::from_name(&word).ok_or_else(|| {
+                        anyhow!(
+                            "unknown field `{}`, expected one of: {}",
+                            word,
+                            FIELDS.join(", ")
+                        )
+                    })?;
+                    let value = match self.advance() {
+                        Some(Token::Word(value) | Token::Quoted(value)) => value,
+                        _ => bail!("expected a value after `{}{}`", word, op.symbol()),
+                    };
+                    predicate(field, op, &value)
+                }
+                _ => Ok(Filter::Name(word.to_lowercase())),
+            },
+            Some(Token::Quoted(text)) => Ok(Filter::Name(text.to_lowercase())),
+            Some(token) => bail!("unexpected {}", token.describe()),
+            None => bail!("expected a filter term"),
+        }
+    }
+}
+
+fn predicate(field: Field, op: Op, value: &str) -> Result<Filter> {
+    if matches!(op, Op::Match | Op::NotMatch) {
+        let regex = Regex::new(value).map_err(|err| {
+            // The regex crate's errors span several lines with a caret diagram; the last line
+            // carries the reason.
+            let message = err.to_string();
+            let reason = message.lines().last().unwrap_or_default().trim();
+            anyhow!(
+                "invalid regex `{}`: {}",
+                value,
+                reason.trim_start_matches("error: ")
+            )
+        })?;
+        let field = match field {
+            Field::Text(field) => field,
+            Field::Number(_) => bail!("`~` only works on text fields (name, cmd, user, state)"),
+        };
+        return Ok(Filter::Regex(field, op == Op::NotMatch, regex));
+    }
+
+    match field {
+        Field::Number(field) => {
+            let parsed = match field {
+                NumberField::Memory => parse_size(value),
+                NumberField::Cpu => value.trim_end_matches('%').parse().ok(),
+                NumberField::CpuTime | NumberField::Elapsed => parse_seconds(value),
+                NumberField::Pid | NumberField::Ppid | NumberField::Threads => value.parse().ok(),
+            };
+            let number = parsed.ok_or_else(|| match field {
+                NumberField::Memory => anyhow!("expected a size such as 500MB, got `{}`", value),
+                NumberField::CpuTime | NumberField::Elapsed => {
+                    anyhow!("expected a duration such as 30s, 5m or 2h, got `{}`", value)
+                }
+                _ => anyhow!("expected a number, got `{}`", value),
+            })?;
+            Ok(Filter::Number(field, op, number))
+        }
+        Field::Text(field) => match op {
+            Op::Eq => Ok(Filter::Text(field, false, value.to_string())),
+            Op::Ne => Ok(Filter::Text(field, true, value.to_string())),
+            op => bail!(
+                "`{}` only works on numeric fields (pid, ppid, cpu, mem, threads, time, elapsed)",
+                op.symbol()
+            ),
+        },
+    }
+}
+
+// Sizes such as "512K", "500MB", "1.5GiB"; a bare number is taken as MB, like the table shows.
+fn parse_size(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier = match unit.to_lowercase().as_str() {
+        "b" => 1.0,
+        "k" | "kb" | "kib" => 1024.0,
+        "" | "m" | "mb" | "mib" => 1024.0 * 1024.0,
+        "g" | "gb" | "gib" => 1024.0 * 1024.0 * 1024.0,
+        "t" | "tb" | "tib" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some(number * multiplier)
+}
+
+// Durations such as "90", "90s", "5m", "1.5h" or "2d"; a bare number is taken as seconds.
+fn parse_seconds(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier = match unit.to_lowercase().as_str() {
+        "" | "s" => 1.0,
+        "m" | "min" => 60.0,
+        "h" => 60.0 * 60.0,
+        "d" => 24.0 * 60.0 * 60.0,
+        _ => return None,
+    };
+    Some(number * multiplier)
+}
+
+//-----------------------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use sysinfo::ProcessStatus;
+
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn item(pid: u32, name: &str) -> ProcessItem {
+        ProcessItem {
+            pid,
+            parent: Some(1),
+            name: name.to_string(),
+            cmd: format!("/usr/bin/{}", name),
+            user: "alice".to_string(),
+            status: ProcessStatus::Sleep,
+            threads: Some(1),
+            nice: None,
+            io_priority: None,
+            cpu_usage: 0.0,
+            memory: 0,
+            virtual_memory: 0,
+            shared_memory: None,
+            start_time: 0,
+            run_time: 0,
+            cpu_time: 0,
+            disk_read: 0,
+            disk_write: 0,
+            tree: None,
+        }
+    }
+
+    fn matches(filter: &str, item: &ProcessItem) -> bool {
+        Filter::parse(filter)
+            .unwrap_or_else(|err| panic!("`{}` failed to parse: {}", filter, err))
+            .matches(item)
+    }
+
+    fn error(filter: &str) -> String {
+        match Filter::parse(filter) {
+            Ok(_) => panic!("`{}` parsed", filter),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let idle = item(1, "init");
+        // pid=1 or (pid=2 and cpu>50), not (pid=1 or pid=2) and cpu>50.
+        assert!(matches("pid=1 or pid=2 and cpu>50", &idle));
+        assert!(matches("pid=2 and cpu>50 or pid=1", &idle));
+        assert!(!matches("(pid=1 or pid=2) and cpu>50", &idle));
+    }
+
+    #[test]
+    fn terms_side_by_side_are_joined_with_and() {
+        let bash = item(7, "bash");
+        assert!(matches("bash pid=7", &bash));
+        assert!(!matches("bash pid=8", &bash));
+        assert!(matches("bash && pid=7 || pid=8", &bash));
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and() {
+        let mut busy = item(2, "yes");
+        busy.cpu_usage = 90.0;
+        assert!(matches("not pid=1 and cpu>50", &busy));
+        assert!(!matches("not pid=2 and cpu>50", &busy));
+        assert!(!matches("not (pid=1 or pid=2)", &busy));
+        assert!(matches("not not pid=2", &busy));
+        assert!(matches("!pid=1", &busy));
+    }
+
+    #[test]
+    fn leading_dash_excludes() {
+        let bash = item(7, "bash");
+        assert!(!matches("-bash", &bash));
+        assert!(matches("-sleep", &bash));
+        assert!(matches("-(pid=1 or pid=2)", &bash));
+        // A dash inside a word is part of it.
+        assert!(matches("ba-sh or bash", &bash));
+        assert!(!matches("ba-sh", &bash));
+    }
+
+    #[test]
+    fn dash_after_an_operator_is_a_sign() {
+        let bash = item(7, "bash");
+        assert!(matches("cpu>-1", &bash));
+        assert!(matches("pid>=-7", &bash));
+        assert!(!matches("pid<-1", &bash));
+    }
+
+    #[test]
+    fn quoted_regexes_keep_their_escapes() {
+        let mut worker = item(9, "python3");
+        worker.cmd = "python3 app.py --worker 12".to_string();
+        assert!(matches(r#"cmd~"--worker \d+""#, &worker));
+        assert!(matches(r"cmd~'worker\s12$'", &worker));
+        assert!(!matches(r#"cmd!~"--worker""#, &worker));
+        assert!(matches(r#"name~"^PY" or name~"^py""#, &worker));
+        // Escaped quotes end up in the value.
+        worker.cmd = r#"sh -c "echo hi""#.to_string();
+        assert!(matches(r#"cmd~"\"echo""#, &worker));
+    }
+
+    #[test]
+    fn quoted_words_filter_by_name() {
+        let mut tmux = item(3, "tmux: server");
+        tmux.cmd = "tmux".to_string();
+        assert!(matches("\"TMUX: ser\"", &tmux));
+    }
+
+    #[test]
+    fn invalid_regexes_are_errors() {
+        let err = error("name~\"(\"");
+        assert!(err.starts_with("invalid regex `(`:"), "{}", err);
+        assert!(!err.contains('\n'), "{}", err);
+        assert_eq!(error("name~\"[a-\""), error("name~'[a-'"));
+        assert_eq!(error("cmd~\"unterminated"), "unterminated quote");
+    }
+
+    #[test]
+    fn memory_takes_size_units() {
+        let mut item = item(4, "postgres");
+        item.memory = 2 * 1024 * MB;
+        assert!(matches("mem>=1.5GiB", &item));
+        assert!(matches("mem>2047MB", &item));
+        assert!(matches("mem>2047", &item));
+        assert!(matches("memory<3g", &item));
+        assert!(!matches("mem>2T", &item));
+        assert!(matches("mem=2147483648b", &item));
+        assert!(matches("mem>1024k", &item));
+        assert_eq!(error("mem>5XB"), "expected a size such as 500MB, got `5XB`");
+    }
+
+    #[test]
+    fn times_take_duration_units() {
+        let mut item = item(5, "make");
+        item.run_time = 2 * 60 * 60;
+        item.cpu_time = 90_500;
+        assert!(matches("elapsed>=2h", &item));
+        assert!(matches("elapsed=7200", &item));
+        assert!(matches("etime<1d", &item));
+        assert!(matches("elapsed>119m and elapsed<121min", &item));
+        assert!(matches("time>90s", &item));
+        assert!(matches("time<1.6m", &item));
+        assert_eq!(
+            error("time>5x"),
+            "expected a duration such as 30s, 5m or 2h, got `5x`"
+        );
+    }
+
+    #[test]
+    fn threads_and_state_compare() {
+        let mut stuck = item(6, "nfsd");
+        stuck.threads = Some(64);
+        stuck.status = ProcessStatus::UninterruptibleDiskSleep;
+        assert!(matches("threads>50", &stuck));
+        assert!(matches("state=D", &stuck));
+        assert!(!matches("state=d", &stuck));
+        assert!(matches("state=\"disk sleep\" or status=D", &stuck));
+        assert!(matches("state~\"^[DZ]$\"", &stuck));
+    }
+
+    #[test]
+    fn unknown_fields_are_reported() {
+        let err = error("foo=1");
+        assert!(
+            err.starts_with("unknown field `foo`, expected one of: pid, ppid"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn operators_must_suit_the_field() {
+        assert_eq!(
+            error("name>5"),
+            "`>` only works on numeric fields (pid, ppid, cpu, mem, threads, time, elapsed)"
+        );
+        assert_eq!(
+            error("pid~5"),
+            "`~` only works on text fields (name, cmd, user, state)"
+        );
+        assert_eq!(error("pid=abc"), "expected a number, got `abc`");
+    }
+
+    #[test]
+    fn incomplete_expressions_are_reported() {
+        assert_eq!(error("cpu>"), "expected a value after `cpu>`");
+        // Right after an operator, keywords are values, so `name=and` works.
+        assert_eq!(error("cpu> and pid=1"), "expected a number, got `and`");
+        assert!(matches("name=and", &item(1, "and")));
+        assert_eq!(error("(pid=1"), "missing `)`");
+        assert_eq!(error("pid=1)"), "unexpected `)`");
+        assert_eq!(error("bash or"), "expected a filter term");
+        assert_eq!(error(">5"), "unexpected `>`");
+    }
+}
@@ -0,0 +1,40 @@
+use std::collections::VecDeque;
+
+//-----------------------------------------------------------------------------------------------------------------
+
+// Fixed-capacity ring buffer of samples; pushing onto a full buffer drops the oldest sample.
+pub struct History<T> {
+    samples: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T: Copy> History<T> {
+    pub fn new(capacity: usize) -> Self {
+        History {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, value: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.samples.len() >= self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(value);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<T> {
+        self.samples.get(index).copied()
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = T> + ExactSizeIterator + '_ {
+        self.samples.iter().copied()
+    }
+}
@@ -0,0 +1,626 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Result, anyhow, bail};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+
+//-----------------------------------------------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Help,
+    MoveUp,
+    MoveDown,
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
+    CommandMode,
+    FilterMode,
+    Search,
+    SearchNext,
+    SearchPrevious,
+    ToggleMark,
+    MarkAll,
+    ClearMarks,
+    SignalPicker,
+    SignalTree,
+    ColumnPicker,
+    Details,
+    NiceUp,
+    NiceDown,
+    IoPriority,
+    Freeze,
+    Throttle,
+    CycleTheme,
+    CycleSort,
+    ReverseSort,
+    ToggleTree,
+    ToggleSubtreeTotals,
+    Collapse,
+    Expand,
+    ToggleCpuCores,
+    ToggleCoreHeatmap,
+}
+
+impl Action {
+    pub const ALL: [Action; 34] = [
+        Action::Quit,
+        Action::Help,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::PageUp,
+        Action::PageDown,
+        Action::Top,
+        Action::Bottom,
+        Action::CommandMode,
+        Action::FilterMode,
+        Action::Search,
+        Action::SearchNext,
+        Action::SearchPrevious,
+        Action::ToggleMark,
+        Action::MarkAll,
+        Action::ClearMarks,
+        Action::SignalPicker,
+        Action::SignalTree,
+        Action::ColumnPicker,
+        Action::Details,
+        Action::NiceUp,
+        Action::NiceDown,
+        Action::IoPriority,
+        Action::Freeze,
+        Action::Throttle,
+        Action::CycleTheme,
+        Action::CycleSort,
+        Action::ReverseSort,
+        Action::ToggleTree,
+        Action::ToggleSubtreeTotals,
+        Action::Collapse,
+        Action::Expand,
+        Action::ToggleCpuCores,
+        Action::ToggleCoreHeatmap,
+    ];
+
+    // The name used for the action in the `[keys]` table of the config file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Help => "help",
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::PageUp => "page_up",
+            Action::PageDown => "page_down",
+            Action::Top => "top",
+            Action::Bottom => "bottom",
+            Action::CommandMode => "command_mode",
+            Action::FilterMode => "filter_mode",
+            Action::Search => "search",
+            Action::SearchNext => "search_next",
+            Action::SearchPrevious => "search_previous",
+            Action::ToggleMark => "toggle_mark",
+            Action::MarkAll => "mark_all",
+            Action::ClearMarks => "clear_marks",
+            Action::SignalPicker => "signal",
+            Action::SignalTree => "signal_tree",
+            Action::ColumnPicker => "columns",
+            Action::Details => "details",
+            Action::NiceUp => "nice_up",
+            Action::NiceDown => "nice_down",
+            Action::IoPriority => "io_priority",
+            Action::Freeze => "freeze",
+            Action::Throttle => "throttle",
+            Action::CycleTheme => "cycle_theme",
+            Action::CycleSort => "cycle_sort",
+            Action::ReverseSort => "reverse_sort",
+            Action::ToggleTree => "toggle_tree",
+            Action::ToggleSubtreeTotals => "toggle_subtree_totals",
+            Action::Collapse => "collapse",
+            Action::Expand => "expand",
+            Action::ToggleCpuCores => "toggle_cpu_cores",
+            Action::ToggleCoreHeatmap => "toggle_core_heatmap",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::Help => "Show this help",
+            Action::MoveUp => "Select previous process",
+            Action::MoveDown => "Select next process",
+            Action::PageUp => "Move one page up",
+            Action::PageDown => "Move one page down",
+            Action::Top => "Jump to first process",
+            Action::Bottom => "Jump to last process",
+            Action::CommandMode => "Enter command mode",
+            Action::FilterMode => "Filter processes",
+            Action::Search => "Search, keeping every process listed",
+            Action::SearchNext => "Jump to the next search match",
+            Action::SearchPrevious => "Jump to the previous search match",
+            Action::ToggleMark => "Mark / unmark the selected process",
+            Action::MarkAll => "Mark every process the filter shows",
+            Action::ClearMarks => "Clear all marks",
+            Action::SignalPicker => "Send a signal to the marked or selected processes",
+            Action::SignalTree => "Send a signal to them and all their descendants",
+            Action::ColumnPicker => "Choose and order the table columns",
+            Action::Details => "Show everything about the selected process",
+            Action::NiceUp => "Raise the nice value (lower the priority)",
+            Action::NiceDown => "Lower the nice value (raise the priority)",
+            Action::IoPriority => "Set the I/O class and level of the marked or selected processes",
+            Action::Freeze => "Suspend the marked or selected processes, or resume them",
+            Action::Throttle => "Cap the CPU usage of the marked or selected processes",
+            Action::CycleTheme => "Cycle themes",
+            Action::CycleSort => "Cycle sort column through the visible columns",
+            Action::ReverseSort => "Reverse the sort order",
+            Action::ToggleTree => "Toggle tree view",
+            Action::ToggleSubtreeTotals => "Toggle subtree CPU/memory totals",
+            Action::Collapse => "Collapse subtree / go to parent",
+            Action::Expand => "Expand subtree",
+            Action::ToggleCpuCores => "Show/hide per-core CPU panel",
+            Action::ToggleCoreHeatmap => "Toggle per-core history heatmap",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q", "ctrl-c"],
+            Action::Help => &["?", "f1"],
+            Action::MoveUp => &["up", "k"],
+            Action::MoveDown => &["down", "j"],
+            Action::PageUp => &["pageup", "ctrl-u"],
+            Action::PageDown => &["pagedown", "ctrl-d"],
+            Action::Top => &["home", "g"],
+            Action::Bottom => &["end", "G"],
+            Action::CommandMode => &[":"],
+            Action::FilterMode => &["/"],
+            Action::Search => &["ctrl-f"],
+            Action::SearchNext => &["n"],
+            Action::SearchPrevious => &["N"],
+            Action::ToggleMark => &["space"],
+            Action::MarkAll => &["m"],
+            Action::ClearMarks => &["U"],
+            Action::SignalPicker => &["x", "f9"],
+            Action::SignalTree => &["X"],
+            Action::ColumnPicker => &["C", "f2"],
+            Action::Details => &["enter"],
+            Action::NiceUp => &["+", "f8"],
+            Action::NiceDown => &["-", "f7"],
+            Action::IoPriority => &["i"],
+            Action::Freeze => &["z"],
+            Action::Throttle => &["Z"],
+            Action::CycleTheme => &["t"],
+            Action::CycleSort => &["c"],
+            Action::ReverseSort => &["r"],
+            Action::ToggleTree => &["T"],
+            Action::ToggleSubtreeTotals => &["a"],
+            Action::Collapse => &["left", "h"],
+            Action::Expand => &["right", "l"],
+            Action::ToggleCpuCores => &["p"],
+            Action::ToggleCoreHeatmap => &["H"],
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        // Shift is already folded into the character for printable keys (and BackTab), so it
+        // is dropped to make "G" and "shift-g" the same chord.
+        let mut modifiers = modifiers;
+        let code = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            KeyCode::BackTab => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::BackTab
+            }
+            code => code,
+        };
+        KeyChord { code, modifiers }
+    }
+
+    pub fn from_event(event: &KeyEvent) -> Self {
+        Self::new(event.code, event.modifiers)
+    }
+
+    // Parses chords such as "j", "G", "ctrl-c", "alt-enter", "shift-tab", "f1" or "pagedown".
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        loop {
+            let lower = rest.to_ascii_lowercase();
+            let (modifier, prefix_len) = if lower.starts_with("ctrl-") {
+                (KeyModifiers::CONTROL, 5)
+            } else if lower.starts_with("alt-") {
+                (KeyModifiers::ALT, 4)
+            } else if lower.starts_with("shift-") {
+                (KeyModifiers::SHIFT, 6)
+            } else {
+                break;
+            };
+            // "ctrl--" binds Ctrl plus the minus key.
+            if rest.len() == prefix_len {
+                break;
+            }
+            modifiers |= modifier;
+            rest = &rest[prefix_len..];
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_ascii_lowercase().as_str() {
+                "space" => KeyCode::Char(' '),
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" | "ins" => KeyCode::Insert,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" | "pgup" => KeyCode::PageUp,
+                "pagedown" | "pgdn" => KeyCode::PageDown,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                name => match name.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                    Some(n) if (1..=24).contains(&n) => KeyCode::F(n),
+                    _ => bail!("unknown key `{}`", s),
+                },
+            },
+        };
+        Ok(Self::new(code, modifiers))
+    }
+
+    pub fn display(&self) -> String {
+        let mut text = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            text.push_str("Ctrl-");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            text.push_str("Alt-");
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            text.push_str("Shift-");
+        }
+        match self.code {
+            KeyCode::Char(' ') => text.push_str("Space"),
+            KeyCode::Char(c) => text.push(c),
+            KeyCode::F(n) => text.push_str(&format!("F{}", n)),
+            KeyCode::PageUp => text.push_str("PgUp"),
+            KeyCode::PageDown => text.push_str("PgDn"),
+            KeyCode::BackTab => text.push_str("Shift-Tab"),
+            code => text.push_str(&format!("{:?}", code)),
+        }
+        text
+    }
+}
+
+//-----------------------------------------------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyList {
+    fn keys(&self) -> Vec<&str> {
+        match self {
+            KeyList::One(key) => vec![key.as_str()],
+            KeyList::Many(keys) => keys.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+pub struct Keymap {
+    bindings: Vec<(KeyChord, Action)>,
+}
+
+impl Keymap {
+    pub fn default_bindings() -> Self {
+        let bindings = Action::ALL
+            .iter()
+            .flat_map(|&action| {
+                action.default_keys().iter().map(move |key| {
+                    let chord = KeyChord::parse(key).expect("default key bindings are valid");
+                    (chord, action)
+                })
+            })
+            .collect();
+        Keymap { bindings }
+    }
+
+    // Each entry in the `[keys]` table replaces all default keys of that action. A key that is
+    // claimed by an override is removed from whatever action it was bound to before.
+    pub fn from_config(overrides: &BTreeMap<String, KeyList>) -> Result<Self> {
+        let mut keymap = Self::default_bindings();
+        for (name, keys) in overrides {
+            let action = Action::from_name(name).ok_or_else(|| {
+                anyhow!(
+                    "unknown action `{}` in [keys], expected one of: {}",
+                    name,
+                    Action::ALL.map(|action| action.name()).join(", ")
+                )
+            })?;
+
+            let mut chords = Vec::new();
+            for key in keys.keys() {
+                let chord =
+                    KeyChord::parse(key).map_err(|err| anyhow!("[keys] {}: {}", name, err))?;
+                // "G" and "shift-g" are the same chord; listing it twice binds it once.
+                if !chords.contains(&chord) {
+                    chords.push(chord);
+                }
+            }
+
+            keymap
+                .bindings
+                .retain(|(chord, bound)| *bound != action && !chords.contains(chord));
+            keymap
+                .bindings
+                .extend(chords.into_iter().map(|chord| (chord, action)));
+        }
+        Ok(keymap)
+    }
+
+    pub fn action(&self, event: &KeyEvent) -> Option<Action> {
+        self.lookup(KeyChord::from_event(event))
+    }
+
+    // The action the key is bound to without Shift, for dialogs where Shift varies a movement:
+    // with the defaults, "J" and Shift-Down both give MoveDown.
+    pub fn unshifted_action(&self, event: &KeyEvent) -> Option<Action> {
+        let chord = KeyChord::from_event(event);
+        let code = match chord.code {
+            KeyCode::Char(c) if c.is_ascii_uppercase() => KeyCode::Char(c.to_ascii_lowercase()),
+            KeyCode::Char(_) => return None,
+            code if chord.modifiers.contains(KeyModifiers::SHIFT) => code,
+            _ => return None,
+        };
+        self.lookup(KeyChord {
+            code,
+            modifiers: chord.modifiers - KeyModifiers::SHIFT,
+        })
+    }
+
+    fn lookup(&self, chord: KeyChord) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == chord)
+            .map(|(_, action)| *action)
+    }
+
+    pub fn keys_for(&self, action: Action) -> Vec<KeyChord> {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| *bound == action)
+            .map(|(chord, _)| *chord)
+            .collect()
+    }
+}
+
+//-----------------------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(code: KeyCode, modifiers: KeyModifiers) -> KeyChord {
+        KeyChord { code, modifiers }
+    }
+
+    fn keymap(entries: &[(&str, &[&str])]) -> Result<Keymap> {
+        let overrides = entries
+            .iter()
+            .map(|(name, keys)| {
+                let keys = keys.iter().map(|key| key.to_string()).collect();
+                (name.to_string(), KeyList::Many(keys))
+            })
+            .collect();
+        Keymap::from_config(&overrides)
+    }
+
+    fn press(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn parses_plain_characters() {
+        let none = KeyModifiers::NONE;
+        assert_eq!(
+            KeyChord::parse("j").unwrap(),
+            chord(KeyCode::Char('j'), none)
+        );
+        assert_eq!(
+            KeyChord::parse("G").unwrap(),
+            chord(KeyCode::Char('G'), none)
+        );
+        assert_eq!(
+            KeyChord::parse("-").unwrap(),
+            chord(KeyCode::Char('-'), none)
+        );
+    }
+
+    #[test]
+    fn parses_named_keys_case_insensitively() {
+        let none = KeyModifiers::NONE;
+        for (text, code) in [
+            ("space", KeyCode::Char(' ')),
+            ("Enter", KeyCode::Enter),
+            ("return", KeyCode::Enter),
+            ("ESC", KeyCode::Esc),
+            ("tab", KeyCode::Tab),
+            ("backtab", KeyCode::BackTab),
+            ("pgup", KeyCode::PageUp),
+            ("PageDown", KeyCode::PageDown),
+            ("del", KeyCode::Delete),
+            ("left", KeyCode::Left),
+            ("f1", KeyCode::F(1)),
+            ("F24", KeyCode::F(24)),
+        ] {
+            assert_eq!(
+                KeyChord::parse(text).unwrap(),
+                chord(code, none),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        for text in ["", "f0", "f25", "pagedn", "ctrl-", "ctrl-foo", "jk"] {
+            assert!(KeyChord::parse(text).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn parses_modifier_combinations() {
+        let ctrl = KeyModifiers::CONTROL;
+        let alt = KeyModifiers::ALT;
+        assert_eq!(
+            KeyChord::parse("ctrl-c").unwrap(),
+            chord(KeyCode::Char('c'), ctrl)
+        );
+        assert_eq!(
+            KeyChord::parse("Alt-Enter").unwrap(),
+            chord(KeyCode::Enter, alt)
+        );
+        assert_eq!(
+            KeyChord::parse("ctrl-alt-x").unwrap(),
+            chord(KeyCode::Char('x'), ctrl | alt)
+        );
+        assert_eq!(
+            KeyChord::parse("ctrl--").unwrap(),
+            chord(KeyCode::Char('-'), ctrl)
+        );
+        assert_eq!(
+            KeyChord::parse("shift-up").unwrap(),
+            chord(KeyCode::Up, KeyModifiers::SHIFT)
+        );
+    }
+
+    #[test]
+    fn shift_folds_into_characters_and_tab() {
+        assert_eq!(
+            KeyChord::parse("shift-g").unwrap(),
+            KeyChord::parse("G").unwrap()
+        );
+        assert_eq!(
+            KeyChord::parse("shift-tab").unwrap(),
+            KeyChord::parse("backtab").unwrap()
+        );
+        // Terminals report Shift along with the uppercase letter.
+        assert_eq!(
+            KeyChord::from_event(&press(KeyCode::Char('G'), KeyModifiers::SHIFT)),
+            KeyChord::parse("G").unwrap()
+        );
+    }
+
+    #[test]
+    fn default_bindings_parse_and_do_not_clash() {
+        let keymap = Keymap::default_bindings();
+        for (i, (chord, action)) in keymap.bindings.iter().enumerate() {
+            for (other, other_action) in &keymap.bindings[i + 1..] {
+                assert!(
+                    chord != other,
+                    "{} is bound to both {} and {}",
+                    chord.display(),
+                    action.name(),
+                    other_action.name()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn override_replaces_the_default_keys() {
+        let keymap = keymap(&[("quit", &["x"])]).unwrap();
+        let quit = press(KeyCode::Char('x'), KeyModifiers::NONE);
+        assert_eq!(keymap.action(&quit), Some(Action::Quit));
+        assert_eq!(
+            keymap.action(&press(KeyCode::Char('q'), KeyModifiers::NONE)),
+            None
+        );
+        // `x` was the signal picker's; it keeps its other default.
+        assert_eq!(
+            keymap.keys_for(Action::SignalPicker),
+            vec![KeyChord::parse("f9").unwrap()]
+        );
+    }
+
+    #[test]
+    fn a_key_claimed_twice_ends_up_bound_once() {
+        // Overrides apply in name order, so the later action keeps the key.
+        let keymap = keymap(&[("help", &["z"]), ("quit", &["z", "z"])]).unwrap();
+        let z = KeyChord::parse("z").unwrap();
+        assert_eq!(
+            keymap
+                .bindings
+                .iter()
+                .filter(|(bound, _)| *bound == z)
+                .count(),
+            1
+        );
+        assert_eq!(
+            keymap.action(&press(KeyCode::Char('z'), KeyModifiers::NONE)),
+            Some(Action::Quit)
+        );
+        assert!(keymap.keys_for(Action::Help).is_empty());
+    }
+
+    #[test]
+    fn rejects_unknown_actions_and_keys() {
+        let err = keymap(&[("explode", &["e"])]).err().unwrap();
+        assert!(err.to_string().contains("unknown action `explode`"));
+        let err = keymap(&[("quit", &["ctrl-nope"])]).err().unwrap();
+        assert!(err.to_string().contains("unknown key `ctrl-nope`"));
+    }
+
+    #[test]
+    fn unshifted_action_follows_remapped_keys() {
+        let keymap = keymap(&[("move_down", &["n"]), ("move_up", &["e", "up"])]).unwrap();
+        let shift = KeyModifiers::SHIFT;
+        let none = KeyModifiers::NONE;
+        assert_eq!(
+            keymap.unshifted_action(&press(KeyCode::Char('N'), shift)),
+            Some(Action::MoveDown)
+        );
+        assert_eq!(
+            keymap.unshifted_action(&press(KeyCode::Char('E'), none)),
+            Some(Action::MoveUp)
+        );
+        assert_eq!(
+            keymap.unshifted_action(&press(KeyCode::Up, shift)),
+            Some(Action::MoveUp)
+        );
+        assert_eq!(
+            keymap.unshifted_action(&press(KeyCode::Char('J'), shift)),
+            None
+        );
+        assert_eq!(
+            keymap.unshifted_action(&press(KeyCode::Char('n'), none)),
+            None
+        );
+        assert_eq!(keymap.unshifted_action(&press(KeyCode::Up, none)), None);
+    }
+}
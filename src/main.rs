@@ -1,22 +1,49 @@
 mod app;
+mod cli;
+mod columns;
+mod command_history;
+mod commands;
+mod config;
+mod details;
+mod export;
+mod filter;
+mod history;
+mod keymap;
+mod priority;
+mod procfs;
+mod signals;
+mod theme;
+mod throttle;
+mod tree;
 mod tui;
 mod ui;
 
 //--------------------------------------------------------------------------------------------------------
 
-use crossterm::event::{self, Event, KeyCode};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent,
+    MouseEventKind,
+};
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
-use app::{App, AppMode, AppTheme};
+use app::{App, AppMode, SignalDialog};
+use clap::Parser;
+use cli::Cli;
+use keymap::{Action, KeyChord};
+use priority::IoClass;
 use ratatui::Terminal;
-use tui::{init, restore};
+use ratatui::layout::Position;
+use tui::{catch_termination, init, restore, termination_requested};
 use ui::ui;
 
 //----------------------------------------------------------------------------------------------------------
 
 fn main() -> Result<()> {
-    let mut terminal = init()?;
-    let mut app = App::new();
+    let cli = Cli::parse();
+    let mut app = App::new(&cli)?;
+    let mut terminal = init(app.mouse)?;
+    catch_termination();
 
     run_app(&mut terminal, &mut app)?;
 
@@ -26,39 +53,160 @@ fn main() -> Result<()> {
 
 //----------------------------------------------------------------------------------------------------------
 
-fn handle_key_event(app: &mut App, key_code: KeyCode) {
+fn handle_action(app: &mut App, action: Action) {
+    match action {
+        Action::Quit => app.should_quit = true,
+        Action::Help => app.open_help(),
+        Action::CommandMode => {
+            app.original_theme = Some(app.theme.clone());
+            app.mode = AppMode::Command;
+            app.command_buffer.clear();
+            app.command_history.reset();
+        }
+        Action::FilterMode => {
+            app.mode = AppMode::Filtering;
+            app.set_filter_query(String::new());
+        }
+        Action::Search => app.start_search(),
+        Action::SearchNext => app.search_next(true),
+        Action::SearchPrevious => app.search_next(false),
+        Action::ToggleMark => app.toggle_mark(),
+        Action::MarkAll => app.mark_all(),
+        Action::ClearMarks => app.clear_marks(),
+        Action::SignalPicker => app.open_signal_dialog(),
+        Action::SignalTree => app.open_tree_signal_dialog(),
+        Action::ColumnPicker => app.open_column_picker(),
+        Action::Details => app.open_details(),
+        Action::NiceUp => app.adjust_nice(1),
+        Action::NiceDown => app.adjust_nice(-1),
+        Action::IoPriority => app.open_io_priority_dialog(),
+        Action::Freeze => app.toggle_freeze(),
+        // The limit is typed as a command; the key just starts the line.
+        Action::Throttle => {
+            app.original_theme = Some(app.theme.clone());
+            app.mode = AppMode::Command;
+            app.command_buffer = "throttle ".to_string();
+            app.command_history.reset();
+        }
+        Action::MoveDown => app.next(),
+        Action::MoveUp => app.previous(),
+        Action::PageDown => app.page_down(),
+        Action::PageUp => app.page_up(),
+        Action::Top => app.select_first(),
+        Action::Bottom => app.select_last(),
+        Action::CycleTheme => app.theme = app.themes.next(&app.theme),
+        Action::CycleSort => app.cycle_sort_coloumn(),
+        Action::ReverseSort => app.reverse_sort(),
+        Action::ToggleCpuCores => app.toggle_cpu_cores(),
+        Action::ToggleCoreHeatmap => app.toggle_core_heatmap(),
+        Action::ToggleTree => app.toggle_tree_view(),
+        Action::ToggleSubtreeTotals => app.toggle_subtree_totals(),
+        Action::Collapse => app.collapse_selected(),
+        Action::Expand => app.expand_selected(),
+    }
+}
+
+fn run_command_line(app: &mut App) {
+    let line = std::mem::take(&mut app.command_buffer);
+    app.original_theme = None;
+    app.mode = AppMode::Normal;
+
+    let recorded = app.command_history.record(&line);
+    if let Err(err) = commands::execute(app, &line) {
+        app.set_error(err.to_string());
+    } else if let Err(err) = recorded {
+        app.set_error(format!("Command history not saved: {:#}", err));
+    }
+}
+
+// Ctrl-R in command mode: typing narrows the search, Ctrl-R steps to older matches, Enter runs the
+// match and any other key takes it into the command line for editing.
+fn handle_history_search(app: &mut App, key: KeyEvent) {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let history = &mut app.command_history;
+    match key.code {
+        KeyCode::Char('r') if ctrl => history.search_older(),
+        KeyCode::Char('g') if ctrl => history.search = None,
+        KeyCode::Esc => history.search = None,
+        KeyCode::Char(c) => history.search_push(c),
+        KeyCode::Backspace => history.search_pop(),
+        code => {
+            if let Some(line) = history.search_match() {
+                app.command_buffer = line.to_string();
+            }
+            app.command_history.search = None;
+            if code == KeyCode::Enter {
+                run_command_line(app);
+            }
+        }
+    }
+}
+
+fn handle_key_event(app: &mut App, key: KeyEvent) {
+    let action = app.keymap.action(&key);
+
+    // Quit chords with Ctrl or Alt (Ctrl-C by default) also work while typing. Shift does not
+    // count: it only makes an uppercase letter, which has to stay typeable.
+    let modifiers = KeyChord::from_event(&key).modifiers;
+    if action == Some(Action::Quit)
+        && modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+    {
+        app.should_quit = true;
+        return;
+    }
+
+    let key_code = key.code;
     match app.mode {
-        AppMode::Normal => match key_code {
-            KeyCode::Char(':') => {
-                app.original_theme = Some(app.theme.clone());
-                app.mode = AppMode::Command;
-                app.command_buffer.clear();
-            }
-            KeyCode::Char('/') => {
-                app.mode = AppMode::Filtering;
-                app.filter_query.clear();
-            }
-            KeyCode::Char('k') => app.kill_selected_process(),
-            KeyCode::Down => app.next(),
-            KeyCode::Up => app.previous(),
-            KeyCode::Char('t') => app.theme = app.theme.clone().next(),
-            KeyCode::Char('c') => app.cycle_sort_coloumn(),
-            _ => {}
+        AppMode::Normal => match action {
+            Some(action) => handle_action(app, action),
+            None if key_code == KeyCode::Esc => app.clear_search(),
+            None => {}
         },
+        AppMode::Help => {
+            let page = app.layout.help.height.saturating_sub(2).max(1) as isize;
+            match action {
+                Some(Action::MoveDown) => app.scroll_help(1),
+                Some(Action::MoveUp) => app.scroll_help(-1),
+                Some(Action::PageDown) => app.scroll_help(page),
+                Some(Action::PageUp) => app.scroll_help(-page),
+                Some(Action::Top) => app.help_scroll = 0,
+                Some(Action::Bottom) => app.help_scroll = usize::MAX,
+                Some(Action::Help | Action::Quit) => app.mode = AppMode::Normal,
+                _ if matches!(key_code, KeyCode::Esc | KeyCode::Enter) => {
+                    app.mode = AppMode::Normal
+                }
+                _ => {}
+            }
+        }
+        AppMode::Details => {
+            let page = app.layout.details.height.saturating_sub(2).max(1) as isize;
+            match action {
+                Some(Action::MoveDown) => app.scroll_details(1),
+                Some(Action::MoveUp) => app.scroll_details(-1),
+                Some(Action::PageDown) => app.scroll_details(page),
+                Some(Action::PageUp) => app.scroll_details(-page),
+                Some(Action::Top) => app.details_scroll = 0,
+                Some(Action::Bottom) => app.details_scroll = usize::MAX,
+                Some(Action::Details | Action::Quit) => app.close_details(),
+                _ if key_code == KeyCode::Esc => app.close_details(),
+                _ => {}
+            }
+        }
+        AppMode::Command if app.command_history.search.is_some() => handle_history_search(app, key),
         AppMode::Command => match key_code {
-            KeyCode::Enter => {
-                let parts: Vec<&str> = app.command_buffer.split_whitespace().collect();
-                if let Some(command) = parts.get(0) {
-                    if *command == "theme" {
-                        if let Some(theme_name) = parts.get(1) {
-                            if let Some(new_theme) = AppTheme::from_str(theme_name) {
-                                app.theme = new_theme;
-                            }
-                        }
-                    }
+            KeyCode::Enter => run_command_line(app),
+            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.command_history.start_search();
+            }
+            KeyCode::Up => {
+                if let Some(line) = app.command_history.previous(&app.command_buffer) {
+                    app.command_buffer = line.to_string();
+                }
+            }
+            KeyCode::Down => {
+                if let Some(line) = app.command_history.next() {
+                    app.command_buffer = line.to_string();
                 }
-                app.original_theme = None;
-                app.mode = AppMode::Normal;
             }
             KeyCode::Char(c) => {
                 app.command_buffer.push(c);
@@ -71,62 +219,279 @@ fn handle_key_event(app: &mut App, key_code: KeyCode) {
                     app.theme = original_theme;
                 }
                 app.original_theme = None;
+                app.command_history.reset();
                 app.mode = AppMode::Normal;
             }
             KeyCode::Tab => {
-                let parts: Vec<&str> = app.command_buffer.split_whitespace().collect();
-                if let Some(command) = parts.get(0) {
-                    if *command == "theme" {
-                        let current_theme_name = parts.get(1).unwrap_or(&"").to_lowercase();
-                        let theme_variants = AppTheme::variants();
-                        let current_index = theme_variants
-                            .iter()
-                            .position(|&v| v.to_lowercase() == current_theme_name);
-                        let next_theme_name = match current_index {
-                            Some(index) => {
-                                theme_variants.get(index + 1).unwrap_or(&theme_variants[0])
-                            }
-                            None => &theme_variants[0],
-                        };
-                        app.command_buffer = format!("theme {}", next_theme_name);
-                        if let Some(new_theme) = AppTheme::from_str(next_theme_name) {
-                            app.theme = new_theme;
-                        }
-                    }
+                if let Some(line) = commands::complete(app, &app.command_buffer) {
+                    app.command_buffer = line;
+                    commands::preview(app);
                 }
             }
             _ => {}
         },
         AppMode::Filtering => match key_code {
             KeyCode::Char(c) => {
-                app.filter_query.push(c);
+                let mut query = app.filter_query.clone();
+                query.push(c);
+                app.set_filter_query(query);
             }
             KeyCode::Backspace => {
-                app.filter_query.pop();
+                let mut query = app.filter_query.clone();
+                query.pop();
+                app.set_filter_query(query);
             }
             KeyCode::Enter | KeyCode::Esc => {
                 app.mode = AppMode::Normal;
             }
             _ => {}
         },
+        AppMode::Search => match key_code {
+            KeyCode::Char(c) => {
+                let mut query = app.search_query.clone();
+                query.push(c);
+                app.set_search_query(query);
+            }
+            KeyCode::Backspace => {
+                let mut query = app.search_query.clone();
+                query.pop();
+                app.set_search_query(query);
+            }
+            KeyCode::Enter => app.mode = AppMode::Normal,
+            KeyCode::Esc => app.cancel_search(),
+            _ => {}
+        },
+        AppMode::SignalPicker => {
+            let Some(dialog) = app.signal_dialog.as_mut() else {
+                app.mode = AppMode::Normal;
+                return;
+            };
+
+            if dialog.confirming {
+                match key_code {
+                    KeyCode::Char('y') | KeyCode::Enter => app.send_pending_signal(),
+                    KeyCode::Char('n') | KeyCode::Esc => dialog.confirming = false,
+                    _ => {}
+                }
+                return;
+            }
+
+            match key_code {
+                KeyCode::Char(c) if c.is_ascii_digit() => {
+                    dialog.selected = SignalDialog::custom_index();
+                    dialog.custom_number.push(c);
+                }
+                _ if action == Some(Action::MoveDown) => dialog.next(),
+                _ if action == Some(Action::MoveUp) => dialog.previous(),
+                KeyCode::Backspace => {
+                    dialog.custom_number.pop();
+                }
+                KeyCode::Enter => {
+                    if dialog.choice().is_some() {
+                        dialog.confirming = true;
+                    } else {
+                        app.set_error("Type a signal number for the custom entry");
+                    }
+                }
+                KeyCode::Esc => app.close_signal_dialog(),
+                _ => {}
+            }
+        }
+        AppMode::IoPriority => {
+            let Some(dialog) = app.io_priority_dialog.as_mut() else {
+                app.mode = AppMode::Normal;
+                return;
+            };
+            match key_code {
+                KeyCode::Char(c) if c.is_ascii_digit() => {
+                    dialog.set_level(c.to_digit(10).unwrap_or_default() as u8)
+                }
+                _ if action == Some(Action::MoveDown) => dialog.next(),
+                _ if action == Some(Action::MoveUp) => dialog.previous(),
+                _ if action == Some(Action::Collapse) => dialog.change_level(-1),
+                _ if action == Some(Action::Expand) => dialog.change_level(1),
+                KeyCode::Enter => app.apply_io_priority_dialog(),
+                KeyCode::Esc => app.close_io_priority_dialog(),
+                _ => {}
+            }
+        }
+        AppMode::ColumnPicker => {
+            let Some(picker) = app.column_picker.as_mut() else {
+                app.mode = AppMode::Normal;
+                return;
+            };
+            // Shift with a movement key moves the column itself.
+            let unshifted = app.keymap.unshifted_action(&key);
+            match key_code {
+                _ if action == Some(Action::MoveDown) => picker.next(),
+                _ if action == Some(Action::MoveUp) => picker.previous(),
+                _ if unshifted == Some(Action::MoveDown) => picker.move_selected(true),
+                _ if unshifted == Some(Action::MoveUp) => picker.move_selected(false),
+                KeyCode::Char(' ') => picker.toggle(),
+                KeyCode::Enter => app.apply_column_picker(),
+                KeyCode::Esc => app.close_column_picker(),
+                _ => {}
+            }
+        }
+    }
+}
+
+// The wheel moves the selection this many rows per notch.
+const SCROLL_ROWS: usize = 3;
+
+fn handle_mouse_event(app: &mut App, mouse: MouseEvent) {
+    let (x, y) = (mouse.column, mouse.row);
+    match app.mode {
+        AppMode::Normal => match mouse.kind {
+            MouseEventKind::ScrollDown => app.scroll_down(SCROLL_ROWS),
+            MouseEventKind::ScrollUp => app.scroll_up(SCROLL_ROWS),
+            MouseEventKind::Down(MouseButton::Left) => {
+                if let Some(column) = app.layout.column_at(x, y) {
+                    if mouse.modifiers.contains(KeyModifiers::CONTROL) {
+                        app.secondary_sort_by_column(column);
+                    } else {
+                        app.sort_by_column(column);
+                    }
+                } else if let Some(index) = app.layout.row_at(x, y, app.table_state.offset()) {
+                    app.select_row(index);
+                } else if app.layout.cpu_cores.contains(Position { x, y }) {
+                    app.toggle_core_heatmap();
+                }
+            }
+            _ => {}
+        },
+        AppMode::Help => match mouse.kind {
+            MouseEventKind::ScrollDown => app.scroll_help(SCROLL_ROWS as isize),
+            MouseEventKind::ScrollUp => app.scroll_help(-(SCROLL_ROWS as isize)),
+            MouseEventKind::Down(_) => app.mode = AppMode::Normal,
+            _ => {}
+        },
+        AppMode::SignalPicker => {
+            let Some(dialog) = app.signal_dialog.as_mut() else {
+                return;
+            };
+            let layout = &app.layout;
+            let position = Position { x, y };
+            match mouse.kind {
+                MouseEventKind::ScrollDown if !dialog.confirming => dialog.next(),
+                MouseEventKind::ScrollUp if !dialog.confirming => dialog.previous(),
+                MouseEventKind::Down(MouseButton::Left) => {
+                    if !layout.dialog.is_some_and(|area| area.contains(position)) {
+                        app.close_signal_dialog();
+                    } else if dialog.confirming {
+                        if layout.confirm_send.contains(position) {
+                            app.send_pending_signal();
+                        } else if layout.confirm_back.contains(position) {
+                            dialog.confirming = false;
+                        }
+                    } else if layout.dialog_list.contains(position) {
+                        // A click picks a signal; clicking the picked one again moves on to confirm.
+                        let index = (y - layout.dialog_list.y) as usize;
+                        if index == dialog.selected && dialog.choice().is_some() {
+                            dialog.confirming = true;
+                        } else {
+                            dialog.selected = index;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        AppMode::ColumnPicker => {
+            let Some(picker) = app.column_picker.as_mut() else {
+                return;
+            };
+            let layout = &app.layout;
+            let position = Position { x, y };
+            match mouse.kind {
+                MouseEventKind::ScrollDown => picker.next(),
+                MouseEventKind::ScrollUp => picker.previous(),
+                MouseEventKind::Down(MouseButton::Left) => {
+                    if !layout.dialog.is_some_and(|area| area.contains(position)) {
+                        app.close_column_picker();
+                    } else if layout.dialog_list.contains(position) {
+                        // A click picks a column; clicking the picked one again shows or hides it.
+                        let index = (y - layout.dialog_list.y) as usize;
+                        if index == picker.selected {
+                            picker.toggle();
+                        } else if index < picker.columns.len() {
+                            picker.selected = index;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        AppMode::IoPriority => {
+            let Some(dialog) = app.io_priority_dialog.as_mut() else {
+                return;
+            };
+            let layout = &app.layout;
+            let position = Position { x, y };
+            match mouse.kind {
+                MouseEventKind::ScrollDown => dialog.next(),
+                MouseEventKind::ScrollUp => dialog.previous(),
+                MouseEventKind::Down(MouseButton::Left) => {
+                    if !layout.dialog.is_some_and(|area| area.contains(position)) {
+                        app.close_io_priority_dialog();
+                    } else if layout.dialog_list.contains(position) {
+                        // A click picks a class; clicking the picked one again applies it.
+                        let class = IoClass::ALL.get((y - layout.dialog_list.y) as usize);
+                        match class {
+                            Some(&class) if class == dialog.priority.class => {
+                                app.apply_io_priority_dialog()
+                            }
+                            Some(&class) => dialog.priority.class = class,
+                            None => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        AppMode::Details => match mouse.kind {
+            MouseEventKind::ScrollDown => app.scroll_details(SCROLL_ROWS as isize),
+            MouseEventKind::ScrollUp => app.scroll_details(-(SCROLL_ROWS as isize)),
+            _ => {}
+        },
+        AppMode::Command | AppMode::Filtering | AppMode::Search => {}
     }
 }
 
 //-----------------------------------------------------------------------------------------------------------
 
+// A termination signal does not end the poll for events, so it is only noticed once the poll
+// times out. Waking this often keeps a long refresh interval from delaying the exit, and with
+// it the release of throttled processes.
+const TERMINATION_CHECK: Duration = Duration::from_millis(250);
+
 fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Result<()> {
+    let mut last_refresh: Option<Instant> = None;
     loop {
-        app.refresh();
+        match last_refresh {
+            Some(at) if at.elapsed() < app.refresh_interval => app.update_processes(),
+            _ => {
+                app.refresh();
+                last_refresh = Some(Instant::now());
+            }
+        }
         terminal.draw(|f| ui(f, app))?;
 
-        if event::poll(std::time::Duration::from_millis(250))? {
-            if let Event::Key(key) = event::read()? {
-                if key.code == KeyCode::Char('q') {
-                    return Ok(());
-                }
-                handle_key_event(app, key.code);
+        let timeout = last_refresh
+            .map(|at| app.refresh_interval.saturating_sub(at.elapsed()))
+            .unwrap_or_default()
+            .min(TERMINATION_CHECK);
+        if event::poll(timeout)? {
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => handle_key_event(app, key),
+                Event::Mouse(mouse) => handle_mouse_event(app, mouse),
+                _ => {}
             }
         }
+
+        if app.should_quit || termination_requested() {
+            return Ok(());
+        }
     }
 }
 
@@ -0,0 +1,163 @@
+use std::io;
+
+//-----------------------------------------------------------------------------------------------------------------
+
+pub const NICE_RANGE: std::ops::RangeInclusive<i32> = -20..=19;
+
+#[cfg(unix)]
+pub fn set_nice(pid: u32, nice: i32) -> io::Result<()> {
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, pid as libc::id_t, nice) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(unix))]
+pub fn set_nice(_pid: u32, _nice: i32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "changing priorities is only supported on Unix",
+    ))
+}
+
+//-----------------------------------------------------------------------------------------------------------------
+
+// Levels within the realtime and best-effort classes, 0 being the most favoured.
+pub const IO_LEVELS: std::ops::RangeInclusive<u8> = 0..=7;
+pub const DEFAULT_IO_LEVEL: u8 = 4;
+
+// The I/O scheduling classes of ioprio_set(2). `None` leaves it to the kernel, which derives a
+// best-effort level from the nice value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IoClass {
+    None,
+    Realtime,
+    BestEffort,
+    Idle,
+}
+
+impl IoClass {
+    pub const ALL: [IoClass; 4] = [
+        IoClass::None,
+        IoClass::Realtime,
+        IoClass::BestEffort,
+        IoClass::Idle,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IoClass::None => "none",
+            IoClass::Realtime => "realtime",
+            IoClass::BestEffort => "best-effort",
+            IoClass::Idle => "idle",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            IoClass::None => "follow the nice value",
+            IoClass::Realtime => "always first (root only)",
+            IoClass::BestEffort => "share by level",
+            IoClass::Idle => "only when the disk is idle",
+        }
+    }
+
+    // Accepts the names `ionice` uses as well. Its class numbers are left out so they cannot be
+    // mistaken for a PID or a level.
+    pub fn from_name(name: &str) -> Option<IoClass> {
+        match name.to_lowercase().as_str() {
+            "none" => Some(IoClass::None),
+            "realtime" | "rt" => Some(IoClass::Realtime),
+            "best-effort" | "besteffort" | "be" => Some(IoClass::BestEffort),
+            "idle" => Some(IoClass::Idle),
+            _ => None,
+        }
+    }
+
+    pub fn has_level(&self) -> bool {
+        matches!(self, IoClass::Realtime | IoClass::BestEffort)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct IoPriority {
+    pub class: IoClass,
+    pub level: u8,
+}
+
+impl IoPriority {
+    // "be/4", "rt/0", "idle" or "none", like the IO column shows it.
+    pub fn label(&self) -> String {
+        match self.class {
+            IoClass::None => "none".to_string(),
+            IoClass::Realtime => format!("rt/{}", self.level),
+            IoClass::BestEffort => format!("be/{}", self.level),
+            IoClass::Idle => "idle".to_string(),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod ioprio {
+    // From linux/ioprio.h; libc does not carry these.
+    pub const WHO_PROCESS: libc::c_int = 1;
+    pub const CLASS_SHIFT: u32 = 13;
+    pub const LEVEL_MASK: u32 = (1 << CLASS_SHIFT) - 1;
+}
+
+#[cfg(target_os = "linux")]
+pub fn io_priority(pid: u32) -> io::Result<IoPriority> {
+    let value = unsafe { libc::syscall(libc::SYS_ioprio_get, ioprio::WHO_PROCESS, pid) };
+    if value < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let value = value as u32;
+    let class = match value >> ioprio::CLASS_SHIFT {
+        1 => IoClass::Realtime,
+        2 => IoClass::BestEffort,
+        3 => IoClass::Idle,
+        _ => IoClass::None,
+    };
+    Ok(IoPriority {
+        class,
+        level: (value & ioprio::LEVEL_MASK) as u8,
+    })
+}
+
+#[cfg(target_os = "linux")]
+pub fn set_io_priority(pid: u32, priority: IoPriority) -> io::Result<()> {
+    let class: u32 = match priority.class {
+        IoClass::None => 0,
+        IoClass::Realtime => 1,
+        IoClass::BestEffort => 2,
+        IoClass::Idle => 3,
+    };
+    let level = if priority.class.has_level() {
+        priority.level as u32
+    } else {
+        0
+    };
+    let value = (class << ioprio::CLASS_SHIFT) | level;
+    if unsafe { libc::syscall(libc::SYS_ioprio_set, ioprio::WHO_PROCESS, pid, value) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn io_priority(_pid: u32) -> io::Result<IoPriority> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "I/O priorities are only supported on Linux",
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_io_priority(_pid: u32, _priority: IoPriority) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "I/O priorities are only supported on Linux",
+    ))
+}
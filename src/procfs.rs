@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+use crate::priority::{self, IoPriority};
+
+//-----------------------------------------------------------------------------------------------------------------
+
+// Figures sysinfo does not collect. Only read while a shown column or a sort key needs them, since
+// it means opening two files and making a system call per process on every refresh.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ProcExtra {
+    pub nice: Option<i32>,
+    pub shared_memory: Option<u64>,
+    pub io_priority: Option<IoPriority>,
+}
+
+#[cfg(target_os = "linux")]
+pub fn read(pid: u32) -> ProcExtra {
+    use std::fs;
+
+    // The name in field 2 may contain spaces and parentheses, so fields are counted from the
+    // last ')'. Nice is field 19, the 17th after it.
+    let nice = fs::read_to_string(format!("/proc/{}/stat", pid))
+        .ok()
+        .and_then(|stat| {
+            let (_, fields) = stat.rsplit_once(')')?;
+            fields.split_whitespace().nth(16)?.parse().ok()
+        });
+    // statm is in pages: size, resident, shared, ...
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(0) as u64;
+    let shared_memory = fs::read_to_string(format!("/proc/{}/statm", pid))
+        .ok()
+        .and_then(|statm| statm.split_whitespace().nth(2)?.parse::<u64>().ok())
+        .map(|pages| pages * page_size);
+    ProcExtra {
+        nice,
+        shared_memory,
+        io_priority: priority::io_priority(pid).ok(),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read(_pid: u32) -> ProcExtra {
+    ProcExtra::default()
+}
+
+// CPU time used so far, user and system together. Read straight from /proc because the throttle
+// samples it from its own thread, without a `System` to refresh.
+#[cfg(target_os = "linux")]
+pub fn cpu_time(pid: u32) -> Option<Duration> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let (_, fields) = stat.rsplit_once(')')?;
+    // utime and stime are fields 14 and 15, in clock ticks.
+    let mut fields = fields.split_whitespace().skip(11);
+    let user: u64 = fields.next()?.parse().ok()?;
+    let system: u64 = fields.next()?.parse().ok()?;
+    let ticks_per_second = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks_per_second <= 0 {
+        return None;
+    }
+    Some(Duration::from_secs_f64(
+        (user + system) as f64 / ticks_per_second as f64,
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn cpu_time(_pid: u32) -> Option<Duration> {
+    None
+}
+
+// Resident memory split by kind as /proc/<pid>/status reports it, plus what is swapped out.
+#[derive(Debug, Default)]
+pub struct MemoryBreakdown {
+    pub anonymous: Option<u64>,
+    pub file: Option<u64>,
+    pub shared: Option<u64>,
+    pub swap: Option<u64>,
+}
+
+#[cfg(target_os = "linux")]
+pub fn memory_breakdown(pid: u32) -> MemoryBreakdown {
+    let mut breakdown = MemoryBreakdown::default();
+    let Ok(status) = std::fs::read_to_string(format!("/proc/{}/status", pid)) else {
+        return breakdown;
+    };
+    for line in status.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        // Values are in kB, e.g. "RssAnon:\t    1234 kB".
+        let bytes = value
+            .trim()
+            .trim_end_matches("kB")
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .map(|kb| kb * 1024);
+        match key {
+            "RssAnon" => breakdown.anonymous = bytes,
+            "RssFile" => breakdown.file = bytes,
+            "RssShmem" => breakdown.shared = bytes,
+            "VmSwap" => breakdown.swap = bytes,
+            _ => {}
+        }
+    }
+    breakdown
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn memory_breakdown(_pid: u32) -> MemoryBreakdown {
+    MemoryBreakdown::default()
+}
@@ -0,0 +1,138 @@
+use std::io;
+
+use serde::Deserialize;
+use sysinfo::{Pid, Signal, System};
+
+//-----------------------------------------------------------------------------------------------------------------
+
+pub struct SignalInfo {
+    pub signal: Signal,
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+pub const SIGNALS: [SignalInfo; 8] = [
+    SignalInfo {
+        signal: Signal::Term,
+        name: "TERM",
+        description: "terminate gracefully",
+    },
+    SignalInfo {
+        signal: Signal::Kill,
+        name: "KILL",
+        description: "kill immediately",
+    },
+    SignalInfo {
+        signal: Signal::Hangup,
+        name: "HUP",
+        description: "hang up / reload",
+    },
+    SignalInfo {
+        signal: Signal::Interrupt,
+        name: "INT",
+        description: "interrupt (Ctrl-C)",
+    },
+    SignalInfo {
+        signal: Signal::Stop,
+        name: "STOP",
+        description: "suspend",
+    },
+    SignalInfo {
+        signal: Signal::Continue,
+        name: "CONT",
+        description: "resume",
+    },
+    SignalInfo {
+        signal: Signal::User1,
+        name: "USR1",
+        description: "user-defined 1",
+    },
+    SignalInfo {
+        signal: Signal::User2,
+        name: "USR2",
+        description: "user-defined 2",
+    },
+];
+
+// Which end of a process subtree is signalled first. Children first lets every parent see its
+// children exit and reap them; parents first stops a supervisor before it can restart workers.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TreeSignalOrder {
+    ChildrenFirst,
+    ParentsFirst,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SignalChoice {
+    Named(Signal),
+    Custom(i32),
+}
+
+impl SignalChoice {
+    pub fn label(&self) -> String {
+        match self {
+            SignalChoice::Named(signal) => SIGNALS
+                .iter()
+                .find(|info| info.signal == *signal)
+                .map(|info| format!("SIG{}", info.name))
+                .unwrap_or_else(|| format!("{:?}", signal)),
+            SignalChoice::Custom(number) => format!("signal {}", number),
+        }
+    }
+}
+
+// Accepts "TERM", "SIGTERM", "term" or a raw signal number.
+pub fn parse_signal(s: &str) -> Option<SignalChoice> {
+    if let Ok(number) = s.parse() {
+        return Some(SignalChoice::Custom(number));
+    }
+    let upper = s.to_ascii_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    SIGNALS
+        .iter()
+        .find(|info| info.name == name)
+        .map(|info| SignalChoice::Named(info.signal))
+}
+
+//-----------------------------------------------------------------------------------------------------------------
+
+pub fn send_signal(sys: &System, pid: u32, choice: SignalChoice) -> io::Result<()> {
+    match choice {
+        SignalChoice::Named(signal) => {
+            let Some(process) = sys.process(Pid::from_u32(pid)) else {
+                return Err(io::Error::new(io::ErrorKind::NotFound, "no such process"));
+            };
+            match process.kill_with(signal) {
+                Some(true) => Ok(()),
+                // sysinfo only reports a bool; errno still holds the reason from the kill(2) call.
+                Some(false) => Err(io::Error::last_os_error()),
+                None => Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "signal not supported on this platform",
+                )),
+            }
+        }
+        SignalChoice::Custom(number) => send_raw_signal(pid, number),
+    }
+}
+
+#[cfg(unix)]
+pub fn send_raw_signal(pid: u32, number: i32) -> io::Result<()> {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return Err(io::Error::from_raw_os_error(libc::ESRCH));
+    };
+    if unsafe { libc::kill(pid, number) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(unix))]
+pub fn send_raw_signal(_pid: u32, _number: i32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "custom signal numbers are only supported on Unix",
+    ))
+}
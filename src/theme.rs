@@ -0,0 +1,292 @@
+use std::fs;
+
+use anyhow::{Context, Result, anyhow, bail};
+use ratatui::style::Color;
+use serde::{Deserialize, Deserializer};
+
+use crate::config::config_dir;
+
+//-----------------------------------------------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy)]
+pub struct ColorTheme {
+    pub base: Color,
+    pub mantle: Color,
+    pub text: Color,
+    pub mauve: Color,
+    pub pink: Color,
+    pub yellow: Color,
+    pub highlight_bg: Color,
+    pub highlight_fg: Color,
+}
+
+#[derive(Debug, Clone)]
+pub struct AppTheme {
+    pub name: String,
+    pub colors: ColorTheme,
+}
+
+impl AppTheme {
+    pub fn get_colors(&self) -> ColorTheme {
+        self.colors
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.name
+    }
+}
+
+pub const DEFAULT_THEME: &str = "GitHub";
+
+// The one list of built-in themes; completion, `t` cycling and lookups all go through it.
+const BUILTIN_THEMES: [(&str, ColorTheme); 6] = [
+    (
+        "Nord",
+        ColorTheme {
+            base: Color::Rgb(46, 52, 64),
+            mantle: Color::Rgb(46, 52, 64),
+            text: Color::Rgb(216, 222, 233),
+            mauve: Color::Rgb(163, 190, 140),
+            pink: Color::Rgb(180, 142, 173),
+            yellow: Color::Rgb(136, 192, 208),
+            highlight_bg: Color::Rgb(76, 86, 106),
+            highlight_fg: Color::Rgb(236, 239, 244),
+        },
+    ),
+    (
+        "Gruvbox",
+        ColorTheme {
+            base: Color::Rgb(40, 40, 40),
+            mantle: Color::Rgb(40, 40, 40),
+            text: Color::Rgb(235, 219, 178),
+            mauve: Color::Rgb(104, 157, 106),
+            pink: Color::Rgb(214, 93, 14),
+            yellow: Color::Rgb(215, 153, 33),
+            highlight_bg: Color::Rgb(69, 133, 136),
+            highlight_fg: Color::Rgb(40, 40, 40),
+        },
+    ),
+    (
+        "SolarizedDark",
+        ColorTheme {
+            base: Color::Rgb(0, 43, 54),
+            mantle: Color::Rgb(0, 43, 54),
+            text: Color::Rgb(131, 148, 150),
+            mauve: Color::Rgb(42, 161, 152),
+            pink: Color::Rgb(38, 139, 210),
+            yellow: Color::Rgb(181, 137, 0),
+            highlight_bg: Color::Rgb(88, 110, 117),
+            highlight_fg: Color::Rgb(0, 43, 54),
+        },
+    ),
+    (
+        "OrangeSunset",
+        ColorTheme {
+            base: Color::Rgb(245, 124, 0),
+            mantle: Color::Rgb(245, 124, 0),
+            text: Color::Rgb(253, 253, 253),
+            mauve: Color::Rgb(0, 180, 255),
+            pink: Color::Rgb(0, 180, 255),
+            yellow: Color::Rgb(255, 224, 178),
+            highlight_bg: Color::Rgb(245, 245, 245),
+            highlight_fg: Color::Rgb(255, 165, 0),
+        },
+    ),
+    (
+        "GitHub",
+        ColorTheme {
+            base: Color::Rgb(13, 17, 23),
+            mantle: Color::Rgb(13, 17, 23),
+            text: Color::Rgb(201, 209, 217),
+            mauve: Color::Rgb(88, 166, 255),
+            pink: Color::Rgb(188, 140, 255),
+            yellow: Color::Rgb(139, 148, 158),
+            highlight_bg: Color::Rgb(33, 38, 45),
+            highlight_fg: Color::Rgb(88, 166, 255),
+        },
+    ),
+    (
+        "MononokaiPro",
+        ColorTheme {
+            base: Color::Rgb(45, 42, 46),
+            mantle: Color::Rgb(45, 42, 46),
+            text: Color::Rgb(252, 252, 250),
+            mauve: Color::Rgb(169, 220, 118),
+            pink: Color::Rgb(255, 216, 102),
+            yellow: Color::Rgb(255, 97, 136),
+            highlight_bg: Color::Rgb(120, 220, 232),
+            highlight_fg: Color::Rgb(252, 252, 250),
+        },
+    ),
+];
+
+//-----------------------------------------------------------------------------------------------------------------
+
+pub struct ThemeRegistry {
+    themes: Vec<AppTheme>,
+}
+
+impl ThemeRegistry {
+    pub fn builtin() -> Self {
+        ThemeRegistry {
+            themes: BUILTIN_THEMES
+                .iter()
+                .map(|(name, colors)| AppTheme {
+                    name: name.to_string(),
+                    colors: *colors,
+                })
+                .collect(),
+        }
+    }
+
+    // Built-ins followed by every `*.toml` file in the themes directory, in file name order.
+    // A user theme with the same name as an existing one replaces it.
+    pub fn load() -> Result<Self> {
+        let mut registry = Self::builtin();
+        let Some(dir) = config_dir().map(|dir| dir.join("themes")) else {
+            return Ok(registry);
+        };
+        let Ok(entries) = fs::read_dir(&dir) else {
+            return Ok(registry);
+        };
+
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            let theme = fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|contents| Ok(toml::from_str::<ThemeFile>(&contents)?))
+                .and_then(|file| {
+                    let fallback_name = path
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    file.into_theme(fallback_name, &registry)
+                })
+                .with_context(|| format!("invalid theme file {}", path.display()))?;
+            registry.insert(theme);
+        }
+        Ok(registry)
+    }
+
+    fn insert(&mut self, theme: AppTheme) {
+        match self
+            .themes
+            .iter_mut()
+            .find(|existing| existing.name.eq_ignore_ascii_case(&theme.name))
+        {
+            Some(existing) => *existing = theme,
+            None => self.themes.push(theme),
+        }
+    }
+
+    pub fn find(&self, name: &str) -> Option<&AppTheme> {
+        self.themes
+            .iter()
+            .find(|theme| theme.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.themes
+            .iter()
+            .map(|theme| theme.name.as_str())
+            .collect()
+    }
+
+    pub fn next(&self, current: &AppTheme) -> AppTheme {
+        let index = self
+            .themes
+            .iter()
+            .position(|theme| theme.name == current.name)
+            .map_or(0, |i| (i + 1) % self.themes.len());
+        self.themes[index].clone()
+    }
+}
+
+//-----------------------------------------------------------------------------------------------------------------
+
+// Roles left out of a theme file are taken from `inherits` (GitHub by default).
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    name: Option<String>,
+    inherits: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_color")]
+    base: Option<Color>,
+    #[serde(default, deserialize_with = "deserialize_color")]
+    mantle: Option<Color>,
+    #[serde(default, deserialize_with = "deserialize_color")]
+    text: Option<Color>,
+    #[serde(default, deserialize_with = "deserialize_color")]
+    mauve: Option<Color>,
+    #[serde(default, deserialize_with = "deserialize_color")]
+    pink: Option<Color>,
+    #[serde(default, deserialize_with = "deserialize_color")]
+    yellow: Option<Color>,
+    #[serde(default, deserialize_with = "deserialize_color")]
+    highlight_bg: Option<Color>,
+    #[serde(default, deserialize_with = "deserialize_color")]
+    highlight_fg: Option<Color>,
+}
+
+impl ThemeFile {
+    fn into_theme(self, fallback_name: String, registry: &ThemeRegistry) -> Result<AppTheme> {
+        let parent_name = self.inherits.as_deref().unwrap_or(DEFAULT_THEME);
+        let parent = registry.find(parent_name).ok_or_else(|| {
+            anyhow!(
+                "unknown theme `{}` in `inherits`, expected one of: {}",
+                parent_name,
+                registry.names().join(", ")
+            )
+        })?;
+        let fallback = parent.colors;
+
+        let name = self.name.unwrap_or(fallback_name);
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            bail!(
+                "theme name `{}` must be a single word, since `:theme` takes one",
+                name
+            );
+        }
+
+        Ok(AppTheme {
+            name,
+            colors: ColorTheme {
+                base: self.base.unwrap_or(fallback.base),
+                mantle: self.mantle.unwrap_or(fallback.mantle),
+                text: self.text.unwrap_or(fallback.text),
+                mauve: self.mauve.unwrap_or(fallback.mauve),
+                pink: self.pink.unwrap_or(fallback.pink),
+                yellow: self.yellow.unwrap_or(fallback.yellow),
+                highlight_bg: self.highlight_bg.unwrap_or(fallback.highlight_bg),
+                highlight_fg: self.highlight_fg.unwrap_or(fallback.highlight_fg),
+            },
+        })
+    }
+}
+
+// Accepts "#rrggbb", an ANSI color name such as "lightblue", or an ANSI index 0-255.
+fn deserialize_color<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Color>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawColor {
+        Index(u8),
+        Text(String),
+    }
+
+    match RawColor::deserialize(deserializer)? {
+        RawColor::Index(index) => Ok(Some(Color::Indexed(index))),
+        RawColor::Text(text) => text.parse().map(Some).map_err(|_| {
+            serde::de::Error::custom(format!(
+                "invalid color `{}`, expected \"#rrggbb\", an ANSI color name or an index 0-255",
+                text
+            ))
+        }),
+    }
+}
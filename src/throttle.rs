@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::procfs;
+use crate::signals::send_raw_signal;
+
+#[cfg(unix)]
+const STOP: i32 = libc::SIGSTOP;
+#[cfg(unix)]
+const CONT: i32 = libc::SIGCONT;
+// Never delivered: raw signals are refused off Unix, so starting a throttle fails there.
+#[cfg(not(unix))]
+const STOP: i32 = 19;
+#[cfg(not(unix))]
+const CONT: i32 = 18;
+
+// One run-then-stop cycle. Short enough that a throttled process looks slow rather than jerky.
+const PERIOD: Duration = Duration::from_millis(100);
+// CPU time is counted in clock ticks, too coarse to measure a single period, so the share is
+// corrected over several.
+const CYCLES_PER_CORRECTION: u32 = 10;
+const MIN_SHARE: f32 = 0.01;
+
+//-----------------------------------------------------------------------------------------------------------------
+
+struct Throttle {
+    limit: f32,
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+// Processes held to a CPU limit, each by a thread of its own alternating SIGCONT and SIGSTOP.
+// Dropping this resumes all of them, so none is left stopped when lazy-top exits.
+#[derive(Default)]
+pub struct Throttles {
+    running: HashMap<u32, Throttle>,
+}
+
+impl Throttles {
+    pub fn len(&self) -> usize {
+        self.running.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.running.is_empty()
+    }
+
+    // The limit in percent of one core, like the CPU column.
+    pub fn limit(&self, pid: u32) -> Option<f32> {
+        self.running.get(&pid).map(|throttle| throttle.limit)
+    }
+
+    // Starts throttling the process, or changes the limit if it already is.
+    pub fn start(&mut self, pid: u32, limit: f32) -> io::Result<()> {
+        // The thread would stop its own process, and with it the only one that could resume it.
+        if pid == std::process::id() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "lazy-top cannot throttle itself",
+            ));
+        }
+        // Signal 0 only checks that the process exists and may be signalled, so a missing
+        // permission is reported here instead of ending the thread silently.
+        send_raw_signal(pid, 0)?;
+        self.release([pid]);
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&stop);
+        let handle = thread::spawn(move || run(pid, limit, &flag));
+        self.running.insert(
+            pid,
+            Throttle {
+                limit,
+                stop,
+                handle,
+            },
+        );
+        Ok(())
+    }
+
+    // Stops throttling the processes and leaves them running, returning how many were
+    // throttled. Every thread is told first so they wind down together rather than a period each.
+    pub fn release(&mut self, pids: impl IntoIterator<Item = u32>) -> usize {
+        let released: Vec<Throttle> = pids
+            .into_iter()
+            .filter_map(|pid| self.running.remove(&pid))
+            .collect();
+        for throttle in &released {
+            throttle.stop.store(true, Ordering::Relaxed);
+        }
+        let count = released.len();
+        for throttle in released {
+            let _ = throttle.handle.join();
+        }
+        count
+    }
+
+    // Drops the throttles of processes `alive` no longer accepts, and of those whose thread gave
+    // up because the process could not be signalled any more.
+    pub fn retain(&mut self, alive: impl Fn(u32) -> bool) {
+        let ended: Vec<u32> = self
+            .running
+            .iter()
+            .filter(|&(&pid, throttle)| !alive(pid) || throttle.handle.is_finished())
+            .map(|(&pid, _)| pid)
+            .collect();
+        self.release(ended);
+    }
+}
+
+impl Drop for Throttles {
+    fn drop(&mut self) {
+        let pids: Vec<u32> = self.running.keys().copied().collect();
+        self.release(pids);
+    }
+}
+
+// Lets the process run for a share of each period and stops it for the rest. The share starts
+// at the limit and is corrected from the CPU time actually used, since a process with several
+// busy threads burns more than one core while it runs, and one waiting on I/O burns less.
+fn run(pid: u32, limit: f32, stop: &AtomicBool) {
+    let mut share = (limit / 100.0).clamp(MIN_SHARE, 1.0);
+    let mut window_start = Instant::now();
+    let mut window_cpu = procfs::cpu_time(pid);
+    let mut cycles = 0;
+
+    while !stop.load(Ordering::Relaxed) {
+        if send_raw_signal(pid, CONT).is_err() {
+            return;
+        }
+        let running = PERIOD.mul_f32(share);
+        thread::sleep(running);
+        if share < 1.0 {
+            if send_raw_signal(pid, STOP).is_err() {
+                return;
+            }
+            thread::sleep(PERIOD - running);
+        }
+
+        cycles += 1;
+        if cycles < CYCLES_PER_CORRECTION {
+            continue;
+        }
+        cycles = 0;
+        let cpu = procfs::cpu_time(pid);
+        if let (Some(before), Some(after)) = (window_cpu, cpu) {
+            let used = (after.saturating_sub(before)).as_secs_f32()
+                / window_start.elapsed().as_secs_f32()
+                * 100.0;
+            // Nothing used says nothing about how fast it would run; otherwise move towards the
+            // limit, at most doubling or halving so one odd window cannot swing it too far.
+            if used > 0.0 {
+                share = (share * (limit / used).clamp(0.5, 2.0)).clamp(MIN_SHARE, 1.0);
+            }
+        }
+        window_start = Instant::now();
+        window_cpu = cpu;
+    }
+    let _ = send_raw_signal(pid, CONT);
+}
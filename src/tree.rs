@@ -0,0 +1,165 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use sysinfo::System;
+
+use crate::app::{ProcessItem, TreeNode};
+use crate::signals::TreeSignalOrder;
+
+//-----------------------------------------------------------------------------------------------------------------
+
+// Reorders an already sorted process list into depth-first tree order. Siblings keep the order
+// they had in the input, so the active sort applies within each level of the tree. Processes whose
+// parent is not in the list (filtered out or gone) become roots.
+pub fn build_tree(processes: Vec<ProcessItem>, collapsed: &HashSet<u32>) -> Vec<ProcessItem> {
+    let index: HashMap<u32, usize> = processes
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (p.pid, i))
+        .collect();
+
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); processes.len()];
+    let mut roots = Vec::new();
+    for (i, process) in processes.iter().enumerate() {
+        match process.parent.and_then(|ppid| index.get(&ppid)) {
+            Some(&parent) if parent != i => children[parent].push(i),
+            _ => roots.push(i),
+        }
+    }
+
+    let mut totals = vec![(0.0, 0); processes.len()];
+    let mut visited = vec![false; processes.len()];
+    for &root in &roots {
+        sum_subtree(root, &processes, &children, &mut totals, &mut visited);
+    }
+    // A parent cycle leaves its members unreachable from any root; show them at the top level.
+    for i in 0..processes.len() {
+        if !visited[i] {
+            roots.push(i);
+            sum_subtree(i, &processes, &children, &mut totals, &mut visited);
+        }
+    }
+
+    let mut slots: Vec<Option<ProcessItem>> = processes.into_iter().map(Some).collect();
+    let mut ordered = Vec::with_capacity(slots.len());
+    let mut walk = TreeWalk {
+        slots: &mut slots,
+        children: &children,
+        totals: &totals,
+        collapsed,
+        ordered: &mut ordered,
+    };
+    for (n, &root) in roots.iter().enumerate() {
+        walk.emit(root, 0, Vec::new(), n + 1 == roots.len());
+    }
+    ordered
+}
+
+fn sum_subtree(
+    i: usize,
+    processes: &[ProcessItem],
+    children: &[Vec<usize>],
+    totals: &mut [(f32, u64)],
+    visited: &mut [bool],
+) -> (f32, u64) {
+    if visited[i] {
+        return (0.0, 0);
+    }
+    visited[i] = true;
+
+    let mut cpu = processes[i].cpu_usage;
+    let mut memory = processes[i].memory;
+    for &child in &children[i] {
+        let (child_cpu, child_memory) = sum_subtree(child, processes, children, totals, visited);
+        cpu += child_cpu;
+        memory += child_memory;
+    }
+    totals[i] = (cpu, memory);
+    (cpu, memory)
+}
+
+struct TreeWalk<'a> {
+    slots: &'a mut [Option<ProcessItem>],
+    children: &'a [Vec<usize>],
+    totals: &'a [(f32, u64)],
+    collapsed: &'a HashSet<u32>,
+    ordered: &'a mut Vec<ProcessItem>,
+}
+
+impl TreeWalk<'_> {
+    fn emit(&mut self, i: usize, depth: usize, guides: Vec<bool>, is_last: bool) {
+        let Some(mut item) = self.slots[i].take() else {
+            return;
+        };
+
+        let is_collapsed = self.collapsed.contains(&item.pid);
+        let (subtree_cpu, subtree_memory) = self.totals[i];
+        item.tree = Some(TreeNode {
+            depth,
+            guides: guides.clone(),
+            is_last,
+            has_children: !self.children[i].is_empty(),
+            collapsed: is_collapsed,
+            subtree_cpu,
+            subtree_memory,
+        });
+        self.ordered.push(item);
+
+        if is_collapsed {
+            return;
+        }
+
+        let mut child_guides = guides;
+        if depth > 0 {
+            child_guides.push(!is_last);
+        }
+        let children = &self.children[i];
+        for (n, &child) in children.iter().enumerate() {
+            self.emit(
+                child,
+                depth + 1,
+                child_guides.clone(),
+                n + 1 == children.len(),
+            );
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------------------------------------------
+
+// Every live process under `roots`, the roots included, found through parent links in the whole
+// process table rather than the filtered list. Breadth-first, so parents always come before their
+// children; reversed for children first.
+pub fn subtree_pids(sys: &System, roots: &[u32], order: TreeSignalOrder) -> Vec<u32> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (pid, process) in sys.processes() {
+        // Threads go with their process.
+        if process.thread_kind().is_some() {
+            continue;
+        }
+        if let Some(parent) = process.parent() {
+            children
+                .entry(parent.as_u32())
+                .or_default()
+                .push(pid.as_u32());
+        }
+    }
+
+    let mut seen: HashSet<u32> = HashSet::new();
+    let mut queue: VecDeque<u32> = roots
+        .iter()
+        .copied()
+        .filter(|&pid| seen.insert(pid))
+        .collect();
+    let mut pids = Vec::new();
+    while let Some(pid) = queue.pop_front() {
+        pids.push(pid);
+        let mut under: Vec<u32> = children.get(&pid).cloned().unwrap_or_default();
+        under.sort_unstable();
+        queue.extend(under.into_iter().filter(|&child| seen.insert(child)));
+    }
+
+    if order == TreeSignalOrder::ChildrenFirst {
+        pids.reverse();
+    }
+    pids
+}
@@ -6,13 +6,18 @@ use crossterm::{
 };
 use ratatui::{Terminal, backend::CrosstermBackend};
 use std::io::{self, Stdout};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 //----------------------------------------------------------------------------------------------------------
 
-pub fn init() -> Result<Terminal<CrosstermBackend<Stdout>>> {
+// Without mouse capture the terminal keeps its own text selection.
+pub fn init(mouse: bool) -> Result<Terminal<CrosstermBackend<Stdout>>> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(stdout, EnterAlternateScreen)?;
+    if mouse {
+        execute!(stdout, EnableMouseCapture)?;
+    }
     let backend = CrosstermBackend::new(stdout);
     let terminal = Terminal::new(backend)?;
     Ok(terminal)
@@ -25,3 +30,32 @@ pub fn restore() -> Result<()> {
     execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture)?;
     Ok(())
 }
+
+//----------------------------------------------------------------------------------------------------------
+
+// Set from a signal handler, which can safely do little more than store a flag.
+static TERMINATE: AtomicBool = AtomicBool::new(false);
+
+// Turns SIGTERM, SIGHUP and SIGINT into an ordinary quit, so the terminal is restored and
+// throttled processes resumed rather than left stopped.
+#[cfg(unix)]
+pub fn catch_termination() {
+    extern "C" fn request(_signal: libc::c_int) {
+        TERMINATE.store(true, Ordering::Relaxed);
+    }
+    for signal in [libc::SIGTERM, libc::SIGHUP, libc::SIGINT] {
+        unsafe {
+            libc::signal(
+                signal,
+                request as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            );
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn catch_termination() {}
+
+pub fn termination_requested() -> bool {
+    TERMINATE.load(Ordering::Relaxed)
+}
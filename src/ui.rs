@@ -1,9 +1,28 @@
 use ratatui::{
+    layout::Flex,
     prelude::*,
-    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Sparkline, Table},
+    widgets::{
+        Axis, Block, Borders, Cell, Chart, Clear, Dataset, GraphType, Paragraph, Row, Sparkline,
+        Table,
+    },
 };
+use sysinfo::ProcessStatus;
 
-use crate::app::{App, AppMode, AppTheme};
+use crate::app::{
+    App, AppMode, ColumnPicker, IoPriorityDialog, MessageKind, ScreenLayout, SignalDialog, Target,
+    TreeNode,
+};
+use crate::columns::Column;
+use crate::commands::{self, COMMANDS};
+use crate::config::format_duration;
+use crate::details;
+use crate::history::History;
+use crate::keymap::Action;
+use crate::priority::{IO_LEVELS, IoClass};
+use crate::signals::{SIGNALS, TreeSignalOrder};
+use crate::theme::ColorTheme;
+
+const GIB: f64 = (1024 * 1024 * 1024) as f64;
 
 //-------------------------------------------------------------------------------------------
 
@@ -14,12 +33,22 @@ pub fn ui(f: &mut Frame, app: &mut App) {
     let base_block = Block::default().style(Style::default().bg(theme.base));
     f.render_widget(base_block, f.area());
 
-    //layout with 3 chunks
+    let core_panel_height = if app.panels.cpu_cores {
+        core_panel_height(app, f.area())
+    } else {
+        0
+    };
+
+    let panels = &app.panels;
+    let graphs_visible = panels.cpu_graph || panels.memory_graph || panels.swap_graph;
+
+    //layout with 5 chunks, hidden panels collapse to zero height
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Length(7),
-            Constraint::Length(5),
+            Constraint::Length(if panels.system { 7 } else { 0 }),
+            Constraint::Length(if graphs_visible { 5 } else { 0 }),
+            Constraint::Length(core_panel_height),
             Constraint::Min(0),
             Constraint::Length(1),
         ])
@@ -46,13 +75,722 @@ pub fn ui(f: &mut Frame, app: &mut App) {
         .block(sys_info_block)
         .style(Style::default().fg(theme.text));
 
-    f.render_widget(sys_info_para, chunks[0]);
+    if app.panels.system {
+        f.render_widget(sys_info_para, chunks[0]);
+    }
+
+    //graphs
+    let mut graph_constraints = Vec::new();
+    if app.panels.cpu_graph {
+        graph_constraints.push(Constraint::Fill(8));
+    }
+    if app.panels.memory_graph {
+        graph_constraints.push(Constraint::Fill(7));
+    }
+    if app.panels.swap_graph {
+        graph_constraints.push(Constraint::Fill(5));
+    }
+    let graph_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(graph_constraints)
+        .split(chunks[1]);
+    let mut graph_areas = graph_chunks.iter().copied();
+
+    if app.panels.cpu_graph {
+        if let Some(area) = graph_areas.next() {
+            render_cpu_sparkline(f, app, area, &theme);
+        }
+    }
+    if app.panels.memory_graph {
+        if let Some(area) = graph_areas.next() {
+            render_memory_chart(f, app, area, &theme);
+        }
+    }
+    if app.panels.swap_graph {
+        if let Some(area) = graph_areas.next() {
+            render_swap_chart(f, app, area, &theme);
+        }
+    }
+
+    //per-core cpu
+    if app.panels.cpu_cores {
+        render_cpu_cores(f, app, chunks[2], &theme);
+    }
+    app.layout.cpu_cores = chunks[2];
+
+    if app.mode == AppMode::Details {
+        render_details(f, app, chunks[3], &theme);
+    } else {
+        render_process_table(f, app, chunks[3], &theme);
+    }
+
+    render_status_bar(f, app, chunks[4], &theme);
+
+    app.layout.dialog = None;
+    if let Some(dialog) = &app.signal_dialog {
+        render_signal_dialog(f, dialog, &mut app.layout, &theme);
+    }
+    if let Some(picker) = &app.column_picker {
+        render_column_picker(f, picker, &mut app.layout, &theme);
+    }
+    if let Some(dialog) = &app.io_priority_dialog {
+        render_io_priority_dialog(f, dialog, &mut app.layout, &theme);
+    }
+
+    if app.mode == AppMode::Help {
+        render_help(f, app, &theme);
+    }
+}
+
+fn render_process_table(f: &mut Frame, app: &mut App, area: Rect, theme: &ColorTheme) {
+    let process_title = match (app.tree_view, app.show_subtree_totals) {
+        (true, true) => "Processes (tree, subtree totals)",
+        (true, false) => "Processes (tree)",
+        _ => "Processes",
+    };
+    let process_block = Block::default()
+        .borders(Borders::ALL)
+        .title(process_title)
+        .border_style(Style::default().fg(theme.pink));
+
+    let header_cells = app.columns.iter().map(|column| {
+        Cell::from(Line::from(column.header()).alignment(column.alignment())).style(
+            Style::default()
+                .fg(theme.yellow)
+                .add_modifier(Modifier::BOLD),
+        )
+    });
+
+    let header = Row::new(header_cells)
+        .height(1)
+        .style(Style::default().bg(theme.mantle));
+
+    let match_style = Style::default()
+        .bg(theme.yellow)
+        .fg(theme.base)
+        .add_modifier(Modifier::BOLD);
+
+    let disk_sleep_style = Style::default().fg(theme.pink).add_modifier(Modifier::BOLD);
+    let throttled_style = Style::default().fg(theme.mauve);
+    let frozen_style = Style::default().fg(theme.pink).add_modifier(Modifier::BOLD);
+
+    let rows = app.processes.iter().map(|item| {
+        let throttle = app.throttles.limit(item.pid);
+        let frozen = throttle.is_none() && app.is_frozen(item.pid);
+        let prefix = item.tree.as_ref().map(tree_prefix).unwrap_or_default();
+        let is_match = app.search_matches(item);
+        let highlight = |text: String| -> Line {
+            if is_match {
+                Line::from(highlight_matches(text, &app.search_query, match_style))
+            } else {
+                Line::from(text)
+            }
+        };
+
+        let cells = app.columns.iter().map(|column| {
+            let text = column.format(item, app.show_subtree_totals);
+            let line = match column {
+                Column::Pid => highlight(text),
+                Column::Name => {
+                    let mut name = highlight(text);
+                    name.spans.insert(0, Span::raw(prefix.clone()));
+                    if let Some(limit) = throttle {
+                        name.spans.push(Span::styled(
+                            format!(" [throttled {}%]", limit),
+                            throttled_style,
+                        ));
+                    } else if frozen {
+                        name.spans.push(Span::styled(" [frozen]", frozen_style));
+                    }
+                    name
+                }
+                Column::State if item.status == ProcessStatus::UninterruptibleDiskSleep => {
+                    Line::from(Span::styled(text, disk_sleep_style))
+                }
+                _ => Line::from(text),
+            };
+            Cell::from(line.alignment(column.alignment()))
+        });
+
+        let row_style = if app.marked.contains(&item.pid) {
+            Style::default()
+                .fg(theme.yellow)
+                .bg(theme.mantle)
+                .add_modifier(Modifier::BOLD)
+        } else if throttle.is_some() {
+            // The whole row is coloured so the state shows even with the name column hidden.
+            throttled_style.bg(theme.base)
+        } else if frozen {
+            frozen_style.bg(theme.base)
+        } else {
+            Style::default().fg(theme.text).bg(theme.base)
+        };
+        Row::new(cells).height(1).style(row_style)
+    });
+
+    let highlight_style = Style::default()
+        .bg(theme.highlight_bg)
+        .fg(theme.highlight_fg)
+        .add_modifier(Modifier::BOLD);
+
+    let table_inner = process_block.inner(area);
+    let widths: Vec<Constraint> = app.columns.iter().map(|column| column.width()).collect();
+    let table = Table::new(rows, widths.clone())
+        .header(header)
+        .block(process_block)
+        .row_highlight_style(highlight_style)
+        .highlight_symbol(">> ");
+    f.render_stateful_widget(table, area, &mut app.table_state);
+    // Borders and header take three rows.
+    app.page_size = (area.height.saturating_sub(3) as usize).max(1);
+    record_table_layout(app, table_inner, widths);
+}
+
+// Repeats the split Table does internally (selection gutter, then the column widths with one
+// cell of spacing) so clicks on the header can be mapped back to columns.
+fn record_table_layout(app: &mut App, inner: Rect, widths: Vec<Constraint>) {
+    let header = Rect {
+        height: inner.height.min(1),
+        ..inner
+    };
+    let selection_width = if app.table_state.selected().is_some() {
+        3
+    } else {
+        0
+    };
+    let [_, columns_area] =
+        Layout::horizontal([Constraint::Length(selection_width), Constraint::Fill(0)])
+            .areas(header);
+    let column_areas = Layout::horizontal(widths)
+        .flex(Flex::Start)
+        .spacing(1)
+        .split(columns_area);
+
+    app.layout.header = header;
+    app.layout.rows = Rect {
+        y: inner.y + header.height,
+        height: inner.height - header.height,
+        ..inner
+    };
+    app.layout.columns = app
+        .columns
+        .iter()
+        .copied()
+        .zip(column_areas.iter().copied())
+        .collect();
+}
+
+// Replaces the process table while open. Long values wrap under their label rather than being
+// cut off, since the full command line and environment are the point of the view.
+fn render_details(f: &mut Frame, app: &mut App, area: Rect, theme: &ColorTheme) {
+    const LABEL_WIDTH: usize = 16;
+    app.layout.details = area;
+    let Some(pid) = app.details_pid else {
+        return;
+    };
+
+    let heading_style = Style::default()
+        .fg(theme.mauve)
+        .add_modifier(Modifier::BOLD);
+    let label_style = Style::default()
+        .fg(theme.yellow)
+        .add_modifier(Modifier::BOLD);
+    let value_width = (area.width.saturating_sub(2) as usize)
+        .saturating_sub(LABEL_WIDTH + 1)
+        .max(1);
+
+    let (name, lines) = match details::collect(app, pid) {
+        Some(sections) => {
+            let name = app
+                .sys
+                .process(sysinfo::Pid::from_u32(pid))
+                .map(|process| process.name().to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let mut lines = Vec::new();
+            for section in sections {
+                if !lines.is_empty() {
+                    lines.push(Line::from(""));
+                }
+                lines.push(Line::from(Span::styled(section.title, heading_style)));
+                for (label, value) in section.rows {
+                    let value = value.replace('\t', " ");
+                    let mut chunks = value.split('\n').flat_map(|line| {
+                        let chars: Vec<char> = line.chars().collect();
+                        let chunks: Vec<String> = chars
+                            .chunks(value_width)
+                            .map(|chunk| chunk.iter().collect())
+                            .collect();
+                        if chunks.is_empty() {
+                            vec![String::new()]
+                        } else {
+                            chunks
+                        }
+                    });
+                    // Labels too long for their column (environment names) get a line of their own.
+                    let own_line = label.chars().count() >= LABEL_WIDTH;
+                    let label = Span::styled(format!(" {:<LABEL_WIDTH$}", label), label_style);
+                    if own_line {
+                        lines.push(Line::from(label));
+                    } else {
+                        lines.push(Line::from(vec![
+                            label,
+                            Span::raw(chunks.next().unwrap_or_default()),
+                        ]));
+                    }
+                    for chunk in chunks {
+                        lines.push(Line::from(format!(" {:<LABEL_WIDTH$}{}", "", chunk)));
+                    }
+                }
+            }
+            (name, lines)
+        }
+        None => (
+            String::new(),
+            vec![Line::from(format!("Process {} has exited.", pid))],
+        ),
+    };
+
+    let visible = area.height.saturating_sub(2) as usize;
+    app.details_scroll = app.details_scroll.min(lines.len().saturating_sub(visible));
+    let position = if lines.len() > visible {
+        format!(
+            " {}-{}/{}",
+            app.details_scroll + 1,
+            app.details_scroll + visible,
+            lines.len()
+        )
+    } else {
+        String::new()
+    };
+    let title = if name.is_empty() {
+        format!("Process {}{} (Esc to go back)", pid, position)
+    } else {
+        format!("Process {} ({}){} (Esc to go back)", pid, name, position)
+    };
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .border_style(Style::default().fg(theme.pink));
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(block)
+            .style(Style::default().fg(theme.text))
+            .scroll((app.details_scroll as u16, 0)),
+        area,
+    );
+}
+
+//----------------------------------------------------------------------------------------------------------------------
+
+// Mode, filter, sort, counts and interval on the left; the latest message fills the rest,
+// right-aligned. Command mode replaces the summary with the command being typed.
+fn render_status_bar(f: &mut Frame, app: &App, area: Rect, theme: &ColorTheme) {
+    let badge = Span::styled(
+        format!(" {} ", app.mode.label()),
+        Style::default()
+            .bg(theme.mauve)
+            .fg(theme.base)
+            .add_modifier(Modifier::BOLD),
+    );
+    let separator = || Span::styled(" │ ", Style::default().fg(theme.mauve));
+    let value = |text: String| Span::styled(text, Style::default().fg(theme.yellow));
+
+    let mut spans = vec![badge, Span::raw(" ")];
+    if let (AppMode::Command, Some(search)) = (&app.mode, &app.command_history.search) {
+        let found = app.command_history.search_match();
+        spans.push(Span::raw(format!(
+            "(history search) '{}': {}_",
+            search.query,
+            found.unwrap_or_default()
+        )));
+        if found.is_none() && !search.query.is_empty() {
+            spans.push(Span::styled(" no match", Style::default().fg(theme.pink)));
+        }
+        spans.push(Span::styled(
+            "  Ctrl-R older  Enter run  Esc cancel",
+            Style::default().fg(theme.mauve),
+        ));
+    } else if app.mode == AppMode::Command {
+        spans.push(Span::raw(format!(":{}_ ", app.command_buffer)));
+        // Usage of the command being typed, or the list of commands to start from.
+        let hint = match app
+            .command_buffer
+            .split_whitespace()
+            .next()
+            .and_then(commands::find)
+        {
+            Some(command) => format!("{} {}  {}", command.name, command.args, command.description),
+            None => COMMANDS.map(|command| command.name).join(" "),
+        };
+        spans.push(Span::styled(hint, Style::default().fg(theme.mauve)));
+    } else {
+        if app.mode == AppMode::Search || !app.search_query.is_empty() {
+            let search = if app.mode == AppMode::Search {
+                format!("{}_", app.search_query)
+            } else {
+                format!("\"{}\"", app.search_query)
+            };
+            spans.extend([
+                Span::raw("search "),
+                value(search),
+                Span::raw(format!(" ({} found)", app.search_match_count())),
+                separator(),
+            ]);
+        }
+        let filter = match (&app.mode, app.filter_query.is_empty()) {
+            (AppMode::Filtering, _) => format!("/{}_", app.filter_query),
+            (_, true) => "none".to_string(),
+            (_, false) => format!("\"{}\"", app.filter_query),
+        };
+        spans.extend([Span::raw("filter "), value(filter)]);
+        if let Some(error) = &app.filter_error {
+            spans.push(Span::styled(
+                format!(" ✗ {}", error),
+                Style::default().fg(theme.pink).add_modifier(Modifier::BOLD),
+            ));
+        }
+        spans.extend([
+            separator(),
+            Span::raw("sort "),
+            value(app.sort_description()),
+            separator(),
+            value(format!("{}/{}", app.processes.len(), app.total_processes)),
+            Span::raw(" procs"),
+        ]);
+        if !app.marked.is_empty() {
+            spans.extend([
+                separator(),
+                value(app.marked.len().to_string()),
+                Span::raw(" marked"),
+            ]);
+        }
+        if app.disk_sleeping > 0 {
+            spans.extend([
+                separator(),
+                Span::styled(
+                    format!("{} in D state", app.disk_sleeping),
+                    Style::default().fg(theme.pink).add_modifier(Modifier::BOLD),
+                ),
+            ]);
+        }
+        if !app.throttles.is_empty() {
+            spans.extend([
+                separator(),
+                value(app.throttles.len().to_string()),
+                Span::raw(" throttled"),
+            ]);
+        }
+        spans.extend([
+            separator(),
+            Span::raw("every "),
+            value(format_duration(app.refresh_interval)),
+        ]);
+    }
+
+    let status = Line::from(spans);
+    let [status_area, message_area] = Layout::horizontal([
+        Constraint::Length(status.width() as u16),
+        Constraint::Fill(1),
+    ])
+    .areas(area);
+    let bar_style = Style::default().fg(theme.text).bg(theme.mantle);
+    f.render_widget(Paragraph::new(status).style(bar_style), status_area);
+
+    let message = app.current_message().map(|message| {
+        let style = match message.kind {
+            MessageKind::Info => Style::default().fg(theme.text),
+            MessageKind::Error => Style::default().fg(theme.pink).add_modifier(Modifier::BOLD),
+        };
+        Line::from(Span::styled(format!("{} ", message.text), style))
+    });
+    f.render_widget(
+        Paragraph::new(message.unwrap_or_default())
+            .alignment(Alignment::Right)
+            .style(bar_style),
+        message_area,
+    );
+}
+
+//----------------------------------------------------------------------------------------------------------------------
+
+fn render_help(f: &mut Frame, app: &mut App, theme: &ColorTheme) {
+    let lines = help_lines(app, theme);
+
+    let area = centered_rect(72, lines.len() as u16 + 2, f.area());
+    let visible = area.height.saturating_sub(2) as usize;
+    app.help_scroll = app.help_scroll.min(lines.len().saturating_sub(visible));
+    app.layout.help = area;
+
+    let title = if lines.len() > visible {
+        format!(
+            "Help {}-{}/{} (Up/Down to scroll, Esc to close)",
+            app.help_scroll + 1,
+            app.help_scroll + visible,
+            lines.len()
+        )
+    } else {
+        "Help (Esc to close)".to_string()
+    };
+
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .border_style(Style::default().fg(theme.mauve))
+        .style(Style::default().bg(theme.base).fg(theme.text));
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(block)
+            .scroll((app.help_scroll as u16, 0)),
+        area,
+    );
+}
+
+fn help_lines(app: &App, theme: &ColorTheme) -> Vec<Line<'static>> {
+    let heading = |text: String| {
+        Line::from(Span::styled(
+            text,
+            Style::default()
+                .fg(theme.mauve)
+                .add_modifier(Modifier::BOLD),
+        ))
+    };
+    let entry = |keys: String, description: String| {
+        Line::from(vec![
+            Span::styled(
+                format!(" {:<22}", keys),
+                Style::default()
+                    .fg(theme.yellow)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(description),
+        ])
+    };
+    let keys = |actions: &[Action]| {
+        actions
+            .iter()
+            .flat_map(|&action| app.keymap.keys_for(action))
+            .map(|chord| chord.display())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let mut lines = vec![heading("Current state".to_string())];
+    let mut sort = format!("{} ({})", app.sort_by.id(), app.sort_order.name());
+    if let Some((column, order)) = app.secondary_sort {
+        sort.push_str(&format!(", then {} ({})", column.id(), order.name()));
+    }
+    lines.push(entry("Sort".to_string(), sort));
+    lines.push(entry(
+        "Filter".to_string(),
+        if app.filter_query.is_empty() {
+            "none".to_string()
+        } else {
+            format!("\"{}\"", app.filter_query)
+        },
+    ));
+    if let Some(pids) = &app.pid_filter {
+        let mut pids: Vec<u32> = pids.iter().copied().collect();
+        pids.sort_unstable();
+        let pids: Vec<String> = pids.iter().map(u32::to_string).collect();
+        lines.push(entry("PIDs".to_string(), pids.join(", ")));
+    }
+    if let Some(uid) = &app.user_filter {
+        lines.push(entry("User".to_string(), format!("uid {}", **uid)));
+    }
+    lines.push(entry(
+        "Tree view".to_string(),
+        if app.tree_view { "on" } else { "off" }.to_string(),
+    ));
+
+    lines.push(Line::from(""));
+    lines.push(heading("Normal mode".to_string()));
+    lines.extend(
+        Action::ALL
+            .iter()
+            .map(|&action| entry(keys(&[action]), action.description().to_string())),
+    );
+    if app.mouse {
+        lines.push(entry("Click".to_string(), "Select a process".to_string()));
+        lines.push(entry(
+            "Click header".to_string(),
+            "Sort by column, again to reverse".to_string(),
+        ));
+        lines.push(entry(
+            "Ctrl-click header".to_string(),
+            "Sort ties by column, again to reverse, then off".to_string(),
+        ));
+        lines.push(entry(
+            "Wheel".to_string(),
+            "Scroll the process list".to_string(),
+        ));
+    }
+
+    lines.push(Line::from(""));
+    lines.push(heading(format!(
+        "Command mode ({})",
+        keys(&[Action::CommandMode])
+    )));
+    for command in &COMMANDS {
+        lines.push(entry(
+            format!("{} {}", command.name, command.args),
+            command.description.to_string(),
+        ));
+    }
+    for (usage, description) in [
+        ("Tab", "Complete the command or argument"),
+        ("Up, Down", "Recall earlier commands"),
+        ("Ctrl-r", "Search earlier commands"),
+        ("Enter", "Run the command"),
+        ("Esc", "Cancel, restoring a previewed theme"),
+    ] {
+        lines.push(entry(usage.to_string(), description.to_string()));
+    }
+
+    lines.push(Line::from(""));
+    lines.push(heading(format!(
+        "Filter mode ({})",
+        keys(&[Action::FilterMode])
+    )));
+    for (usage, description) in [
+        ("Type", "Filter as you type, see the syntax below"),
+        ("Backspace", "Delete the last character"),
+        ("Enter, Esc", "Leave filter mode, keeping the filter"),
+        ("firefox", "Name contains the word"),
+        (
+            "cpu>5 mem>=500MB",
+            "Compare pid, ppid, cpu or mem: = != < <= > >=",
+        ),
+        (
+            "threads>50 time>10m",
+            "Also threads, CPU time and elapsed time",
+        ),
+        (
+            "user=root state=Z",
+            "Match user, state, name or cmd exactly",
+        ),
+        ("cmd~\"--worker\"", "Regex match, !~ for no match"),
+        ("a and b, a or b", "Combine terms; side by side means and"),
+        ("not a, -a, ( )", "Exclude, group"),
+    ] {
+        lines.push(entry(usage.to_string(), description.to_string()));
+    }
+    lines.push(entry(
+        format!("{}, Enter", keys(&[Action::FilterMode])),
+        "Clear the filter".to_string(),
+    ));
+
+    lines.push(Line::from(""));
+    lines.push(heading(format!(
+        "Search mode ({})",
+        keys(&[Action::Search])
+    )));
+    for (usage, description) in [
+        ("Type", "Jump to the first name or PID that matches"),
+        ("Enter", "Keep the search highlighted"),
+        ("Esc", "Clear the search and go back"),
+    ] {
+        lines.push(entry(usage.to_string(), description.to_string()));
+    }
+    lines.push(entry(
+        keys(&[Action::SearchNext, Action::SearchPrevious]),
+        "Next / previous match (normal mode)".to_string(),
+    ));
+    lines.push(entry(
+        "Esc".to_string(),
+        "Clear a kept search (normal mode)".to_string(),
+    ));
+
+    lines.push(Line::from(""));
+    lines.push(heading(format!(
+        "Signal picker ({})",
+        keys(&[Action::SignalPicker])
+    )));
+    for (usage, description) in [
+        ("Up, Down", "Choose a signal"),
+        ("0-9", "Type a custom signal number"),
+        ("Enter", "Continue to the confirmation"),
+        ("y, Enter / n, Esc", "Send / go back when confirming"),
+        ("Esc", "Close the picker"),
+    ] {
+        lines.push(entry(usage.to_string(), description.to_string()));
+    }
+
+    lines.push(Line::from(""));
+    lines.push(heading(format!(
+        "Column picker ({})",
+        keys(&[Action::ColumnPicker])
+    )));
+    for (usage, description) in [
+        ("Up, Down", "Choose a column"),
+        ("Space", "Show or hide it"),
+        ("J, K", "Move it later / earlier in the table"),
+        ("Enter", "Apply and save to the config file"),
+        ("Esc", "Close without changes"),
+    ] {
+        lines.push(entry(usage.to_string(), description.to_string()));
+    }
 
-    //graph
+    lines.push(Line::from(""));
+    lines.push(heading(format!(
+        "I/O priority ({})",
+        keys(&[Action::IoPriority])
+    )));
+    for (usage, description) in [
+        ("Up, Down", "Choose a class"),
+        ("Left, Right, 0-7", "Choose the level, 0 is served first"),
+        ("Enter", "Apply"),
+        ("Esc", "Close without changes"),
+    ] {
+        lines.push(entry(usage.to_string(), description.to_string()));
+    }
+
+    lines.push(Line::from(""));
+    lines.push(heading(format!(
+        "Process details ({})",
+        keys(&[Action::Details])
+    )));
+    lines.push(entry(
+        keys(&[Action::MoveUp, Action::MoveDown]),
+        "Scroll".to_string(),
+    ));
+    lines.push(entry(
+        keys(&[Action::PageUp, Action::PageDown]),
+        "Scroll one page".to_string(),
+    ));
+    lines.push(entry(
+        format!("{}, Esc", keys(&[Action::Details])),
+        "Back to the process list".to_string(),
+    ));
+
+    lines.push(Line::from(""));
+    lines.push(heading("Help".to_string()));
+    lines.push(entry(
+        keys(&[Action::MoveUp, Action::MoveDown]),
+        "Scroll".to_string(),
+    ));
+    lines.push(entry(
+        keys(&[Action::PageUp, Action::PageDown]),
+        "Scroll one page".to_string(),
+    ));
+    lines.push(entry(
+        format!("{}, Esc", keys(&[Action::Help])),
+        "Close".to_string(),
+    ));
+    lines
+}
+
+//----------------------------------------------------------------------------------------------------------------------
+
+fn render_cpu_sparkline(f: &mut Frame, app: &App, area: Rect, theme: &ColorTheme) {
+    // Only the newest samples that fit inside the borders are drawn.
+    let visible = area.width.saturating_sub(2) as usize;
     let cpu_history_data: Vec<u64> = app
         .cpu_history
         .iter()
-        .map(|&(_, usage)| usage as u64)
+        .skip(app.cpu_history.len().saturating_sub(visible))
+        .map(|usage| usage as u64)
         .collect();
 
     let sparkline = Sparkline::default()
@@ -65,68 +803,577 @@ pub fn ui(f: &mut Frame, app: &mut App) {
         .data(&cpu_history_data)
         .style(Style::default().fg(theme.mauve));
 
-    f.render_widget(sparkline, chunks[1]);
+    f.render_widget(sparkline, area);
+}
 
-    //Process table
-    let process_block = Block::default()
+fn render_memory_chart(f: &mut Frame, app: &App, area: Rect, theme: &ColorTheme) {
+    let total = app.sys.total_memory();
+    let history = &app.memory_history;
+    let used = history_points(&history.used, total);
+    let available = history_points(&history.available, total);
+    let cached = history_points(&history.cached, total);
+
+    let title = Line::from(vec![
+        Span::raw("Memory ("),
+        Span::styled("used", Style::default().fg(theme.pink)),
+        Span::raw(" / "),
+        Span::styled("avail", Style::default().fg(theme.mauve)),
+        Span::raw(" / "),
+        Span::styled("cached", Style::default().fg(theme.yellow)),
+        Span::raw(" %)"),
+    ]);
+
+    let datasets = vec![
+        history_dataset(&cached, theme.yellow),
+        history_dataset(&available, theme.mauve),
+        history_dataset(&used, theme.pink),
+    ];
+    render_history_chart(f, datasets, title, app.history_len, area, theme);
+}
+
+fn render_swap_chart(f: &mut Frame, app: &App, area: Rect, theme: &ColorTheme) {
+    let total = app.sys.total_swap();
+    let title = if total == 0 {
+        Line::from("Swap (none)")
+    } else {
+        Line::from(format!(
+            "Swap ({:.1} / {:.1} GiB)",
+            app.sys.used_swap() as f64 / GIB,
+            total as f64 / GIB
+        ))
+    };
+
+    let used = history_points(&app.memory_history.swap_used, total);
+    let datasets = vec![history_dataset(&used, theme.pink)];
+    render_history_chart(f, datasets, title, app.history_len, area, theme);
+}
+
+// Plots samples as a percentage of `total`.
+fn history_points(history: &History<u64>, total: u64) -> Vec<(f64, f64)> {
+    if total == 0 {
+        return Vec::new();
+    }
+    history
+        .iter()
+        .enumerate()
+        .map(|(i, value)| (i as f64, value as f64 / total as f64 * 100.0))
+        .collect()
+}
+
+fn history_dataset(points: &[(f64, f64)], color: Color) -> Dataset<'_> {
+    Dataset::default()
+        .marker(symbols::Marker::Braille)
+        .graph_type(GraphType::Line)
+        .style(Style::default().fg(color))
+        .data(points)
+}
+
+fn render_history_chart(
+    f: &mut Frame,
+    datasets: Vec<Dataset>,
+    title: Line,
+    history_len: usize,
+    area: Rect,
+    theme: &ColorTheme,
+) {
+    let chart = Chart::new(datasets)
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.pink)),
+        )
+        .style(Style::default().bg(theme.base))
+        .x_axis(Axis::default().bounds([0.0, history_len.saturating_sub(1) as f64]))
+        .y_axis(Axis::default().bounds([0.0, 100.0]));
+    f.render_widget(chart, area);
+}
+
+//----------------------------------------------------------------------------------------------------------------------
+
+// Up to this many cores get one detailed line each; more than that switch to the compact grid.
+const DETAILED_CORE_LIMIT: usize = 8;
+const GRID_CELL_WIDTH: u16 = 24;
+
+fn core_panel_height(app: &App, area: Rect) -> u16 {
+    let cores = app.sys.cpus().len();
+    let rows = if app.panels.core_heatmap {
+        cores.div_ceil(2)
+    } else if cores <= DETAILED_CORE_LIMIT {
+        cores
+    } else {
+        let columns = (area.width.saturating_sub(2) / GRID_CELL_WIDTH).max(1) as usize;
+        cores.div_ceil(columns)
+    };
+    (rows as u16 + 2).min((area.height / 3).max(3))
+}
+
+fn render_cpu_cores(f: &mut Frame, app: &App, area: Rect, theme: &ColorTheme) {
+    let cpus = app.sys.cpus();
+    let title = if app.panels.core_heatmap {
+        format!("CPU Cores ({}) - history heatmap", cpus.len())
+    } else {
+        format!("CPU Cores ({})", cpus.len())
+    };
+    let block = Block::default()
         .borders(Borders::ALL)
-        .title("Processes")
+        .title(title)
         .border_style(Style::default().fg(theme.pink));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
 
-    let header_cells = ["PID", "Name", "CPU %", "Memory"].iter().map(|h| {
-        Cell::from(*h).style(
-            Style::default()
-                .fg(theme.yellow)
-                .add_modifier(Modifier::BOLD),
-        )
-    });
+    let lines: Vec<Line> = if app.panels.core_heatmap {
+        core_heatmap_lines(app, inner.width, theme)
+    } else if cpus.len() <= DETAILED_CORE_LIMIT {
+        let bar_width = inner.width.saturating_sub(26) as usize;
+        cpus.iter()
+            .enumerate()
+            .map(|(i, cpu)| {
+                let mut spans = vec![Span::raw(format!("cpu{:<3}", i))];
+                spans.extend(usage_bar(cpu.cpu_usage(), bar_width, theme));
+                spans.push(Span::raw(format!(
+                    " {:>5.1}% {:>5} MHz",
+                    cpu.cpu_usage(),
+                    cpu.frequency()
+                )));
+                Line::from(spans)
+            })
+            .collect()
+    } else {
+        let columns = (inner.width / GRID_CELL_WIDTH).max(1) as usize;
+        cpus.chunks(columns)
+            .enumerate()
+            .map(|(row, chunk)| {
+                let mut spans = Vec::new();
+                for (column, cpu) in chunk.iter().enumerate() {
+                    spans.push(Span::raw(format!("{:>3} ", row * columns + column)));
+                    spans.extend(usage_bar(cpu.cpu_usage(), 6, theme));
+                    spans.push(Span::raw(format!(
+                        "{:>4.0}% {:>3.1}G  ",
+                        cpu.cpu_usage(),
+                        cpu.frequency() as f64 / 1000.0
+                    )));
+                }
+                Line::from(spans)
+            })
+            .collect()
+    };
 
-    let header = Row::new(header_cells)
-        .height(1)
-        .style(Style::default().bg(theme.mantle));
+    f.render_widget(
+        Paragraph::new(lines).style(Style::default().fg(theme.text)),
+        inner,
+    );
+}
 
-    let rows = app.processes.iter().map(|item| {
-        let cells = vec![
-            Cell::from(item.pid.clone()),
-            Cell::from(item.name.clone()),
-            Cell::from(item.cpu_usage.clone()),
-            Cell::from(item.memory.clone()),
+fn usage_bar(usage: f32, width: usize, theme: &ColorTheme) -> Vec<Span<'static>> {
+    let filled = ((usage.clamp(0.0, 100.0) / 100.0) * width as f32).round() as usize;
+    let color = if usage >= 80.0 {
+        theme.pink
+    } else {
+        theme.mauve
+    };
+    vec![
+        Span::raw("["),
+        Span::styled("|".repeat(filled), Style::default().fg(color)),
+        Span::raw(" ".repeat(width - filled)),
+        Span::raw("]"),
+    ]
+}
+
+// Each text row shows two cores using the upper half block: foreground for the even core,
+// background for the odd one. Newest samples are on the right.
+fn core_heatmap_lines(app: &App, width: u16, theme: &ColorTheme) -> Vec<Line<'static>> {
+    let samples = width.saturating_sub(8) as usize;
+    let column = |history: &History<f32>, x: usize| {
+        let start = samples.saturating_sub(history.len());
+        x.checked_sub(start)
+            .and_then(|i| history.get(history.len().saturating_sub(samples) + i))
+    };
+
+    app.core_history
+        .chunks(2)
+        .enumerate()
+        .map(|(row, pair)| {
+            let label = match pair.len() {
+                2 => format!("{:>3}/{:<3} ", row * 2, row * 2 + 1),
+                _ => format!("{:>3}     ", row * 2),
+            };
+            let mut spans = vec![Span::raw(label)];
+            for x in 0..samples {
+                let top = column(&pair[0], x).map_or(theme.base, |u| heat_color(theme, u));
+                let bottom = pair
+                    .get(1)
+                    .and_then(|history| column(history, x))
+                    .map_or(theme.base, |u| heat_color(theme, u));
+                spans.push(Span::styled("▀", Style::default().fg(top).bg(bottom)));
+            }
+            Line::from(spans)
+        })
+        .collect()
+}
+
+fn heat_color(theme: &ColorTheme, usage: f32) -> Color {
+    let t = (usage / 100.0).clamp(0.0, 1.0);
+    if t < 0.5 {
+        blend(theme.base, theme.mauve, t * 2.0)
+    } else {
+        blend(theme.mauve, theme.pink, (t - 0.5) * 2.0)
+    }
+}
+
+fn blend(from: Color, to: Color, t: f32) -> Color {
+    match (from, to) {
+        (Color::Rgb(r1, g1, b1), Color::Rgb(r2, g2, b2)) => {
+            let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+            Color::Rgb(mix(r1, r2), mix(g1, g2), mix(b1, b2))
+        }
+        _ if t < 0.5 => from,
+        _ => to,
+    }
+}
+
+//----------------------------------------------------------------------------------------------------------------------
+
+fn render_signal_dialog(
+    f: &mut Frame,
+    dialog: &SignalDialog,
+    layout: &mut ScreenLayout,
+    theme: &ColorTheme,
+) {
+    const SEND_HINT: &str = "y / Enter: send";
+    const BACK_HINT: &str = "n / Esc: back";
+
+    // Several targets get a wider dialog, tall enough to list every one of them if it can.
+    let width: u16 = if dialog.targets.len() > 1 { 72 } else { 54 };
+    let title = match &dialog.subtree {
+        Some(subtree) => match subtree.roots.as_slice() {
+            [root] => format!("Send signal to the subtree of {} ({})", root.pid, root.name),
+            roots => format!(
+                "Send signal to the subtrees of {} marked processes",
+                roots.len()
+            ),
+        },
+        None => format!("Send signal to {}", describe_targets(&dialog.targets)),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .border_style(Style::default().fg(theme.mauve))
+        .style(Style::default().bg(theme.base).fg(theme.text));
+
+    let lines: Vec<Line> = if dialog.confirming {
+        let label = dialog
+            .choice()
+            .map(|choice| choice.label())
+            .unwrap_or_default();
+        let order = match &dialog.subtree {
+            Some(subtree) if dialog.targets.len() > 1 => match subtree.order {
+                TreeSignalOrder::ChildrenFirst => ", children first",
+                TreeSignalOrder::ParentsFirst => ", parents first",
+            },
+            _ => "",
+        };
+        let mut lines = vec![
+            Line::from(""),
+            Line::from(match dialog.targets.as_slice() {
+                [target] => format!("Send {} to PID {}?", label, target.pid),
+                targets => format!("Send {} to {} processes{}?", label, targets.len(), order),
+            }),
         ];
+        // Borders, the question, the hints and the blank lines around them take six rows.
+        let list_rows = f.area().height.saturating_sub(6).max(1) as usize;
+        match dialog.targets.as_slice() {
+            [target] => lines.push(Line::from(format!("({})", target.name))),
+            targets => lines.extend(
+                wrap_targets(targets, width.saturating_sub(2) as usize, list_rows)
+                    .into_iter()
+                    .map(Line::from),
+            ),
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::from(vec![
+            Span::styled(SEND_HINT, Style::default().fg(theme.yellow)),
+            Span::raw("    "),
+            Span::styled(BACK_HINT, Style::default().fg(theme.yellow)),
+        ]));
+        lines
+    } else {
+        let highlight = Style::default()
+            .bg(theme.highlight_bg)
+            .fg(theme.highlight_fg)
+            .add_modifier(Modifier::BOLD);
 
-        let row_style = Style::default().fg(theme.text).bg(theme.base);
-        Row::new(cells).height(1).style(row_style)
-    });
+        let mut lines: Vec<Line> = SIGNALS
+            .iter()
+            .enumerate()
+            .map(|(i, info)| {
+                let text = format!(" SIG{:<6} {}", info.name, info.description);
+                if i == dialog.selected {
+                    Line::from(Span::styled(text, highlight))
+                } else {
+                    Line::from(text)
+                }
+            })
+            .collect();
 
-    let highlight_style = Style::default()
+        let custom = format!(" Custom:   {}_", dialog.custom_number);
+        lines.push(if dialog.selected == SignalDialog::custom_index() {
+            Line::from(Span::styled(custom, highlight))
+        } else {
+            Line::from(custom)
+        });
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            "Up/Down: choose  0-9: custom  Enter: next  Esc: cancel",
+            Style::default().fg(theme.yellow),
+        )));
+        lines
+    };
+
+    let height = (lines.len() as u16 + 2).max(SIGNALS.len() as u16 + 6);
+    let area = centered_rect(width, height, f.area());
+    f.render_widget(Clear, area);
+
+    // The list starts on the first inner row; the confirm screen has its hints on its last line.
+    let inner = block.inner(area);
+    let hint_row = Rect {
+        y: inner.y + lines.len().saturating_sub(1) as u16,
+        height: 1,
+        ..inner
+    }
+    .intersection(inner);
+    layout.dialog = Some(area);
+    layout.dialog_list = Rect {
+        height: SignalDialog::custom_index() as u16 + 1,
+        ..inner
+    }
+    .intersection(inner);
+    layout.confirm_send = Rect {
+        width: SEND_HINT.len() as u16,
+        ..hint_row
+    }
+    .intersection(hint_row);
+    layout.confirm_back = Rect {
+        x: hint_row.x + SEND_HINT.len() as u16 + 4,
+        width: BACK_HINT.len() as u16,
+        ..hint_row
+    }
+    .intersection(hint_row);
+
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn render_column_picker(
+    f: &mut Frame,
+    picker: &ColumnPicker,
+    layout: &mut ScreenLayout,
+    theme: &ColorTheme,
+) {
+    let area = centered_rect(58, picker.columns.len() as u16 + 5, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Columns")
+        .border_style(Style::default().fg(theme.mauve))
+        .style(Style::default().bg(theme.base).fg(theme.text));
+
+    let highlight = Style::default()
+        .bg(theme.highlight_bg)
+        .fg(theme.highlight_fg)
+        .add_modifier(Modifier::BOLD);
+    let mut lines: Vec<Line> = picker
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, (column, shown))| {
+            let text = format!(
+                " [{}] {:<8} {}",
+                if *shown { 'x' } else { ' ' },
+                column.id(),
+                column.description()
+            );
+            if i == picker.selected {
+                Line::from(Span::styled(text, highlight))
+            } else {
+                Line::from(text)
+            }
+        })
+        .collect();
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "Space: show/hide  J/K: move  Enter: save  Esc: cancel",
+        Style::default().fg(theme.yellow),
+    )));
+
+    let inner = block.inner(area);
+    layout.dialog = Some(area);
+    layout.dialog_list = Rect {
+        height: picker.columns.len() as u16,
+        ..inner
+    }
+    .intersection(inner);
+
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn render_io_priority_dialog(
+    f: &mut Frame,
+    dialog: &IoPriorityDialog,
+    layout: &mut ScreenLayout,
+    theme: &ColorTheme,
+) {
+    let area = centered_rect(62, IoClass::ALL.len() as u16 + 6, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(
+            "I/O priority of {}",
+            describe_targets(&dialog.targets)
+        ))
+        .border_style(Style::default().fg(theme.mauve))
+        .style(Style::default().bg(theme.base).fg(theme.text));
+
+    let highlight = Style::default()
         .bg(theme.highlight_bg)
         .fg(theme.highlight_fg)
         .add_modifier(Modifier::BOLD);
+    let mut lines: Vec<Line> = IoClass::ALL
+        .iter()
+        .map(|class| {
+            let text = format!(" {:<12} {}", class.name(), class.description());
+            if *class == dialog.priority.class {
+                Line::from(Span::styled(text, highlight))
+            } else {
+                Line::from(text)
+            }
+        })
+        .collect();
+    lines.push(Line::from(""));
 
-    let table = Table::new(
-        rows,
-        [
-            Constraint::Length(10),
-            Constraint::Min(20),
-            Constraint::Length(10),
-            Constraint::Length(12),
-        ],
-    )
-    .header(header)
-    .block(process_block)
-    .row_highlight_style(highlight_style)
-    .highlight_symbol(">> ");
-    f.render_stateful_widget(table, chunks[2], &mut app.table_state);
-
-    if app.mode == AppMode::Command {
-        let command_text = format!(
-            ":{} (Options: {})",
-            app.command_buffer,
-            AppTheme::variants().join(", ")
-        );
-        let command_paragraph =
-            Paragraph::new(command_text).style(Style::default().fg(theme.text).bg(theme.mantle));
-        f.render_widget(command_paragraph, chunks[3]);
+    // Only realtime and best-effort have levels; the row stays but is dimmed for the others.
+    let has_level = dialog.priority.class.has_level();
+    let mut level = vec![Span::raw(" Level ")];
+    for value in IO_LEVELS {
+        let text = format!(" {} ", value);
+        level.push(if has_level && value == dialog.priority.level {
+            Span::styled(text, highlight)
+        } else {
+            Span::raw(text)
+        });
+    }
+    level.push(Span::raw("  0 is served first"));
+    let mut level = Line::from(level);
+    if !has_level {
+        level = level.style(Style::default().add_modifier(Modifier::DIM));
+    }
+    lines.push(level);
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "Up/Down: class  Left/Right: level  Enter: set  Esc: cancel",
+        Style::default().fg(theme.yellow),
+    )));
+
+    let inner = block.inner(area);
+    layout.dialog = Some(area);
+    layout.dialog_list = Rect {
+        height: IoClass::ALL.len() as u16,
+        ..inner
+    }
+    .intersection(inner);
+
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+// "1234 (name)" for one process, "12 marked processes" for several.
+fn describe_targets(targets: &[Target]) -> String {
+    match targets {
+        [target] => format!("{} ({})", target.pid, target.name),
+        targets => format!("{} marked processes", targets.len()),
+    }
+}
+
+// "1234 name" for every target, wrapped to `width`. When they need more than `max_lines` lines,
+// the last one says how many were left out.
+fn wrap_targets(targets: &[Target], width: usize, max_lines: usize) -> Vec<String> {
+    let mut lines: Vec<Vec<String>> = Vec::new();
+    let mut line_width = 0;
+    for target in targets {
+        let entry = format!("{} {}", target.pid, target.name);
+        let entry_width = entry.chars().count();
+        match lines.last_mut() {
+            Some(line) if line_width + 2 + entry_width <= width => {
+                line_width += 2 + entry_width;
+                line.push(entry);
+            }
+            _ => {
+                line_width = entry_width;
+                lines.push(vec![entry]);
+            }
+        }
+    }
+    if lines.len() > max_lines {
+        let kept = max_lines.saturating_sub(1);
+        let left_out: usize = lines[kept..].iter().map(Vec::len).sum();
+        lines.truncate(kept);
+        lines.push(vec![format!("… and {} more", left_out)]);
+    }
+    lines.into_iter().map(|line| line.join("  ")).collect()
+}
+
+fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+//----------------------------------------------------------------------------------------------------------------------
+
+fn tree_prefix(node: &TreeNode) -> String {
+    let mut prefix = String::new();
+    if node.depth > 0 {
+        for &continues in &node.guides {
+            prefix.push_str(if continues { "│  " } else { "   " });
+        }
+        prefix.push_str(if node.is_last { "└─" } else { "├─" });
+    }
+
+    prefix.push(match (node.has_children, node.collapsed) {
+        (true, true) => '▸',
+        (true, false) => '▾',
+        (false, _) if node.depth > 0 => '─',
+        (false, _) => ' ',
+    });
+    prefix.push(' ');
+    prefix
+}
+
+// Splits `text` so every case-insensitive occurrence of `query` gets `style`.
+fn highlight_matches(text: String, query: &str, style: Style) -> Vec<Span<'static>> {
+    let haystack = text.to_ascii_lowercase();
+    let needle = query.to_ascii_lowercase();
+    let mut spans = Vec::new();
+    let mut last = 0;
+    for (start, found) in haystack.match_indices(&needle) {
+        if start > last {
+            spans.push(Span::raw(text[last..start].to_string()));
+        }
+        spans.push(Span::styled(
+            text[start..start + found.len()].to_string(),
+            style,
+        ));
+        last = start + found.len();
+    }
+    if last < text.len() {
+        spans.push(Span::raw(text[last..].to_string()));
     }
+    spans
 }
 
 //----------------------------------------------------------------------------------------------------------------------